@@ -0,0 +1,380 @@
+use std::fmt;
+use std::str::FromStr;
+
+/** OVERFLOW POLICIES
+ * Rust integer operators panic on overflow in debug builds and wrap silently in release builds
+ * The calculator never relies on that: every operation is evaluated under an explicit policy
+ *  - Checked: overflow is an error (CalcError::Overflow)
+ *  - Wrapping: the result wraps around modulo 2^bits
+ *  - Saturating: the result is clamped to MIN / MAX
+ *  - Overflowing: the wrapped result plus a flag telling whether it overflowed
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    Checked,
+    Wrapping,
+    Saturating,
+    Overflowing,
+}
+
+impl OverflowPolicy {
+    pub const ALL: [OverflowPolicy; 4] = [
+        OverflowPolicy::Checked,
+        OverflowPolicy::Wrapping,
+        OverflowPolicy::Saturating,
+        OverflowPolicy::Overflowing,
+    ];
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OverflowPolicy::Checked => "checked",
+            OverflowPolicy::Wrapping => "wrapping",
+            OverflowPolicy::Saturating => "saturating",
+            OverflowPolicy::Overflowing => "overflowing",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "checked" => Ok(OverflowPolicy::Checked),
+            "wrapping" => Ok(OverflowPolicy::Wrapping),
+            "saturating" => Ok(OverflowPolicy::Saturating),
+            "overflowing" => Ok(OverflowPolicy::Overflowing),
+            other => Err(format!("unknown overflow policy `{}`", other)),
+        }
+    }
+}
+
+// Typed error returned by checked operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalcError {
+    Overflow { operation: &'static str },
+    DivisionByZero,
+    // shift / rotate amount negative or not below the bit width
+    ShiftOutOfRange { bits: u32 },
+    // bit range [start, start + len) not inside the type
+    BitRangeOutOfRange { start: u32, len: u32, bits: u32 },
+    // inserted value has bits set outside of its `len`-bit field
+    FieldTooWide { len: u32 },
+    // negative or fractional exponent on an integer type, or one above u32::MAX
+    InvalidExponent,
+    // square root of a negative number, logarithm of zero or a negative number
+    Domain { operation: &'static str },
+    // min / max / mean of no values at all
+    Empty { operation: &'static str },
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::Overflow { operation } => write!(f, "{} overflow", operation),
+            CalcError::DivisionByZero => write!(f, "Division by zero"),
+            CalcError::ShiftOutOfRange { bits } => {
+                write!(f, "shift amount out of range, must be in 0..{}", bits)
+            }
+            CalcError::BitRangeOutOfRange { start, len, bits } => {
+                write!(f, "bit range {}..{} out of range for a {}-bit type", start, start + len, bits)
+            }
+            CalcError::FieldTooWide { len } => write!(f, "value does not fit in a {}-bit field", len),
+            CalcError::InvalidExponent => write!(f, "exponent must be a whole number in 0..=4294967295"),
+            CalcError::Domain { operation } => write!(f, "{} undefined for this operand", operation),
+            CalcError::Empty { operation } => write!(f, "{} of no values", operation),
+        }
+    }
+}
+
+impl std::error::Error for CalcError {}
+
+/** OVERFLOW ARITHMETIC
+ * Primitive operations of a numeric type under every overflow policy
+ * Integers forward to the inherent checked_* / wrapping_* / saturating_* / overflowing_* methods
+ * Floats never wrap: an operation "overflows" when finite operands give an infinite result
+ * Division by zero is rejected by the Calculator before these are called
+ */
+pub trait OverflowArithmetic: Copy {
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn overflowing_add(self, rhs: Self) -> (Self, bool);
+
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    fn overflowing_sub(self, rhs: Self) -> (Self, bool);
+
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    fn saturating_mul(self, rhs: Self) -> Self;
+    fn overflowing_mul(self, rhs: Self) -> (Self, bool);
+
+    fn checked_div(self, rhs: Self) -> Option<Self>;
+    fn wrapping_div(self, rhs: Self) -> Self;
+    fn saturating_div(self, rhs: Self) -> Self;
+    fn overflowing_div(self, rhs: Self) -> (Self, bool);
+
+    fn is_zero(self) -> bool;
+}
+
+macro_rules! impl_integer_overflow_arithmetic {
+    ($($t:ty),*) => {
+        $(
+            impl OverflowArithmetic for $t {
+                fn checked_add(self, rhs: Self) -> Option<Self> { <$t>::checked_add(self, rhs) }
+                fn wrapping_add(self, rhs: Self) -> Self { <$t>::wrapping_add(self, rhs) }
+                fn saturating_add(self, rhs: Self) -> Self { <$t>::saturating_add(self, rhs) }
+                fn overflowing_add(self, rhs: Self) -> (Self, bool) { <$t>::overflowing_add(self, rhs) }
+
+                fn checked_sub(self, rhs: Self) -> Option<Self> { <$t>::checked_sub(self, rhs) }
+                fn wrapping_sub(self, rhs: Self) -> Self { <$t>::wrapping_sub(self, rhs) }
+                fn saturating_sub(self, rhs: Self) -> Self { <$t>::saturating_sub(self, rhs) }
+                fn overflowing_sub(self, rhs: Self) -> (Self, bool) { <$t>::overflowing_sub(self, rhs) }
+
+                fn checked_mul(self, rhs: Self) -> Option<Self> { <$t>::checked_mul(self, rhs) }
+                fn wrapping_mul(self, rhs: Self) -> Self { <$t>::wrapping_mul(self, rhs) }
+                fn saturating_mul(self, rhs: Self) -> Self { <$t>::saturating_mul(self, rhs) }
+                fn overflowing_mul(self, rhs: Self) -> (Self, bool) { <$t>::overflowing_mul(self, rhs) }
+
+                //E the only overflowing division is MIN / -1 on signed types
+                fn checked_div(self, rhs: Self) -> Option<Self> { <$t>::checked_div(self, rhs) }
+                fn wrapping_div(self, rhs: Self) -> Self { <$t>::wrapping_div(self, rhs) }
+                fn saturating_div(self, rhs: Self) -> Self { <$t>::saturating_div(self, rhs) }
+                fn overflowing_div(self, rhs: Self) -> (Self, bool) { <$t>::overflowing_div(self, rhs) }
+
+                fn is_zero(self) -> bool { self == 0 }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_float_overflow_arithmetic {
+    ($($t:ty),*) => {
+        $(
+            impl OverflowArithmetic for $t {
+                fn checked_add(self, rhs: Self) -> Option<Self> { float_checked(self, rhs, self + rhs) }
+                fn wrapping_add(self, rhs: Self) -> Self { self + rhs }
+                fn saturating_add(self, rhs: Self) -> Self { float_saturate(self + rhs) }
+                fn overflowing_add(self, rhs: Self) -> (Self, bool) { float_overflowing(self, rhs, self + rhs) }
+
+                fn checked_sub(self, rhs: Self) -> Option<Self> { float_checked(self, rhs, self - rhs) }
+                fn wrapping_sub(self, rhs: Self) -> Self { self - rhs }
+                fn saturating_sub(self, rhs: Self) -> Self { float_saturate(self - rhs) }
+                fn overflowing_sub(self, rhs: Self) -> (Self, bool) { float_overflowing(self, rhs, self - rhs) }
+
+                fn checked_mul(self, rhs: Self) -> Option<Self> { float_checked(self, rhs, self * rhs) }
+                fn wrapping_mul(self, rhs: Self) -> Self { self * rhs }
+                fn saturating_mul(self, rhs: Self) -> Self { float_saturate(self * rhs) }
+                fn overflowing_mul(self, rhs: Self) -> (Self, bool) { float_overflowing(self, rhs, self * rhs) }
+
+                fn checked_div(self, rhs: Self) -> Option<Self> { float_checked(self, rhs, self / rhs) }
+                fn wrapping_div(self, rhs: Self) -> Self { self / rhs }
+                fn saturating_div(self, rhs: Self) -> Self { float_saturate(self / rhs) }
+                fn overflowing_div(self, rhs: Self) -> (Self, bool) { float_overflowing(self, rhs, self / rhs) }
+
+                fn is_zero(self) -> bool { self == 0.0 }
+            }
+        )*
+    };
+}
+
+impl_integer_overflow_arithmetic!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+impl_float_overflow_arithmetic!(f32, f64);
+
+/** EXTENDED ARITHMETIC
+ * Negation, absolute value and powers under every overflow policy, plus roots and logarithms
+ *  - negating an unsigned value overflows unless it is 0, `abs(MIN)` overflows on signed types
+ *  - integer exponents are u32 like `pow`, floats raise to any float with `powf`
+ *  - `sqrt`, `log2` and `log10` are integer square root / logarithms rounded down for integers,
+ *    and None outside of their domain (negative roots, logarithms of values <= 0)
+ */
+pub trait ExtendedArithmetic: OverflowArithmetic + PartialOrd {
+    type Exponent: Copy;
+
+    fn checked_neg(self) -> Option<Self>;
+    fn wrapping_neg(self) -> Self;
+    fn saturating_neg(self) -> Self;
+    fn overflowing_neg(self) -> (Self, bool);
+
+    fn checked_abs(self) -> Option<Self>;
+    fn wrapping_abs(self) -> Self;
+    fn saturating_abs(self) -> Self;
+    fn overflowing_abs(self) -> (Self, bool);
+
+    // The value used as an exponent, None when it can't be one
+    fn exponent(self) -> Option<Self::Exponent>;
+    fn checked_pow(self, exp: Self::Exponent) -> Option<Self>;
+    fn wrapping_pow(self, exp: Self::Exponent) -> Self;
+    fn saturating_pow(self, exp: Self::Exponent) -> Self;
+    fn overflowing_pow(self, exp: Self::Exponent) -> (Self, bool);
+
+    fn sqrt(self) -> Option<Self>;
+    fn log2(self) -> Option<Self>;
+    fn log10(self) -> Option<Self>;
+
+    // A count of values as this type, for means; None when it doesn't fit (300 values as an i8)
+    fn from_len(len: usize) -> Option<Self>;
+}
+
+macro_rules! impl_integer_extended_arithmetic {
+    (signed: $($t:ty),*) => {
+        $(
+            impl ExtendedArithmetic for $t {
+                fn checked_neg(self) -> Option<Self> { <$t>::checked_neg(self) }
+                fn wrapping_neg(self) -> Self { <$t>::wrapping_neg(self) }
+                fn saturating_neg(self) -> Self { <$t>::saturating_neg(self) }
+                fn overflowing_neg(self) -> (Self, bool) { <$t>::overflowing_neg(self) }
+
+                fn checked_abs(self) -> Option<Self> { <$t>::checked_abs(self) }
+                fn wrapping_abs(self) -> Self { <$t>::wrapping_abs(self) }
+                fn saturating_abs(self) -> Self { <$t>::saturating_abs(self) }
+                fn overflowing_abs(self) -> (Self, bool) { <$t>::overflowing_abs(self) }
+
+                fn sqrt(self) -> Option<Self> { <$t>::checked_isqrt(self) }
+
+                impl_integer_extended_arithmetic!(@common $t);
+            }
+        )*
+    };
+    (unsigned: $($t:ty),*) => {
+        $(
+            impl ExtendedArithmetic for $t {
+                fn checked_neg(self) -> Option<Self> { <$t>::checked_neg(self) }
+                fn wrapping_neg(self) -> Self { <$t>::wrapping_neg(self) }
+                //E -x <= 0 for every unsigned x, so it saturates to 0
+                fn saturating_neg(self) -> Self { 0 }
+                fn overflowing_neg(self) -> (Self, bool) { <$t>::overflowing_neg(self) }
+
+                fn checked_abs(self) -> Option<Self> { Some(self) }
+                fn wrapping_abs(self) -> Self { self }
+                fn saturating_abs(self) -> Self { self }
+                fn overflowing_abs(self) -> (Self, bool) { (self, false) }
+
+                fn sqrt(self) -> Option<Self> { Some(<$t>::isqrt(self)) }
+
+                impl_integer_extended_arithmetic!(@common $t);
+            }
+        )*
+    };
+    (@common $t:ty) => {
+        type Exponent = u32;
+
+        fn exponent(self) -> Option<u32> { u32::try_from(self).ok() }
+        fn checked_pow(self, exp: u32) -> Option<Self> { <$t>::checked_pow(self, exp) }
+        fn wrapping_pow(self, exp: u32) -> Self { <$t>::wrapping_pow(self, exp) }
+        fn saturating_pow(self, exp: u32) -> Self { <$t>::saturating_pow(self, exp) }
+        fn overflowing_pow(self, exp: u32) -> (Self, bool) { <$t>::overflowing_pow(self, exp) }
+
+        //E the logarithm of any integer type fits in that type, ilog2(u128::MAX) is 127
+        fn log2(self) -> Option<Self> { <$t>::checked_ilog2(self).map(|log| log as $t) }
+        fn log10(self) -> Option<Self> { <$t>::checked_ilog10(self).map(|log| log as $t) }
+
+        fn from_len(len: usize) -> Option<Self> { <$t>::try_from(len).ok() }
+    };
+}
+
+macro_rules! impl_float_extended_arithmetic {
+    ($($t:ty),*) => {
+        $(
+            impl ExtendedArithmetic for $t {
+                type Exponent = $t;
+
+                fn checked_neg(self) -> Option<Self> { Some(-self) }
+                fn wrapping_neg(self) -> Self { -self }
+                fn saturating_neg(self) -> Self { -self }
+                fn overflowing_neg(self) -> (Self, bool) { (-self, false) }
+
+                fn checked_abs(self) -> Option<Self> { Some(self.abs()) }
+                fn wrapping_abs(self) -> Self { self.abs() }
+                fn saturating_abs(self) -> Self { self.abs() }
+                fn overflowing_abs(self) -> (Self, bool) { (self.abs(), false) }
+
+                fn exponent(self) -> Option<Self> { Some(self) }
+                fn checked_pow(self, exp: Self) -> Option<Self> { float_checked(self, exp, self.powf(exp)) }
+                fn wrapping_pow(self, exp: Self) -> Self { self.powf(exp) }
+                fn saturating_pow(self, exp: Self) -> Self { float_saturate(self.powf(exp)) }
+                fn overflowing_pow(self, exp: Self) -> (Self, bool) { float_overflowing(self, exp, self.powf(exp)) }
+
+                fn sqrt(self) -> Option<Self> { (self >= 0.0).then(|| <$t>::sqrt(self)) }
+                fn log2(self) -> Option<Self> { (self > 0.0).then(|| <$t>::log2(self)) }
+                fn log10(self) -> Option<Self> { (self > 0.0).then(|| <$t>::log10(self)) }
+
+                fn from_len(len: usize) -> Option<Self> { Some(len as $t) }
+            }
+        )*
+    };
+}
+
+impl_integer_extended_arithmetic!(signed: i8, i16, i32, i64, i128);
+impl_integer_extended_arithmetic!(unsigned: u8, u16, u32, u64, u128);
+impl_float_extended_arithmetic!(f32, f64);
+
+// Square and multiply for types without an inherent `pow`, the flag is set once any step overflowed
+pub fn overflowing_pow_by_squaring<T: OverflowArithmetic>(base: T, mut exp: u32, one: T) -> (T, bool) {
+    let (mut base, mut result, mut overflowed) = (base, one, false);
+    while exp > 0 {
+        if exp & 1 == 1 {
+            let (value, step) = result.overflowing_mul(base);
+            result = value;
+            overflowed |= step;
+        }
+        exp >>= 1;
+        if exp > 0 {
+            let (value, step) = base.overflowing_mul(base);
+            base = value;
+            overflowed |= step;
+        }
+    }
+    (result, overflowed)
+}
+
+// Float helpers: only finite operands producing an infinite result count as an overflow
+trait Float: Copy {
+    const MAX: Self;
+    const MIN: Self;
+    fn is_finite(self) -> bool;
+    fn is_nan(self) -> bool;
+    fn is_sign_negative(self) -> bool;
+}
+
+impl Float for f32 {
+    const MAX: Self = f32::MAX;
+    const MIN: Self = f32::MIN;
+    fn is_finite(self) -> bool { f32::is_finite(self) }
+    fn is_nan(self) -> bool { f32::is_nan(self) }
+    fn is_sign_negative(self) -> bool { f32::is_sign_negative(self) }
+}
+
+impl Float for f64 {
+    const MAX: Self = f64::MAX;
+    const MIN: Self = f64::MIN;
+    fn is_finite(self) -> bool { f64::is_finite(self) }
+    fn is_nan(self) -> bool { f64::is_nan(self) }
+    fn is_sign_negative(self) -> bool { f64::is_sign_negative(self) }
+}
+
+fn float_overflowing<F: Float>(lhs: F, rhs: F, result: F) -> (F, bool) {
+    (result, lhs.is_finite() && rhs.is_finite() && !result.is_finite())
+}
+
+fn float_checked<F: Float>(lhs: F, rhs: F, result: F) -> Option<F> {
+    match float_overflowing(lhs, rhs, result) {
+        (_, true) => None,
+        (value, false) => Some(value),
+    }
+}
+
+fn float_saturate<F: Float>(result: F) -> F {
+    if result.is_finite() || result.is_nan() {
+        result
+    } else if result.is_sign_negative() {
+        F::MIN
+    } else {
+        F::MAX
+    }
+}
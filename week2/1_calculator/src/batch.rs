@@ -0,0 +1,216 @@
+use std::io::{self, BufRead, Write};
+
+use crate::arithmetic::{CalcError, OverflowPolicy};
+use crate::calculator::Calculator;
+use crate::eval::{dispatch, EvalError, NumType, Operand};
+use crate::registry::{apply, Operation, Registry};
+use crate::report::csv_field;
+
+/** BATCH
+ * Runs a set of registered operations over many (m1, m2) pairs
+ *  - input is one pair per line, `m1,m2` or `m1 m2`; blank lines, `#` comments and an `m1,m2` header are skipped
+ *  - output is CSV, one row per input pair, written as soon as the pair is evaluated
+ *
+ * A Calculator error (division by zero, checked overflow, shift out of range) is the value of its cell,
+ * an input that can't be evaluated at all (bad literal, wrong field count) fills the `error` column,
+ * and neither stops the batch
+ */
+// Used when no --ops is given
+pub const DEFAULT_OPERATIONS: [&str; 4] = ["add", "sub", "mul", "div"];
+
+// "add,mul,shl" -> ["add", "mul", "shl"]
+pub fn parse_names(list: &str) -> Vec<String> {
+    list.split(',').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect()
+}
+
+// Result of one operation on one pair: the value or the Calculator error, and whether it overflowed
+type Outcome = (Result<String, String>, bool);
+
+fn evaluate_pair<T: Operand>(
+    m1: &str,
+    m2: &str,
+    operations: &[&dyn Operation<T>],
+    policy: OverflowPolicy,
+) -> Result<Vec<Outcome>, EvalError> {
+    let parse = |literal: &str| {
+        T::parse_literal(literal).ok_or_else(|| EvalError::InvalidLiteral {
+            literal: literal.to_string(),
+            type_name: T::NAME,
+        })
+    };
+    let calc = Calculator { m1: parse(m1)?, m2: parse(m2)? };
+
+    Ok(operations
+        .iter()
+        .map(|operation| match apply(*operation, &calc, policy) {
+            Some(Ok((value, overflowed))) => (Ok(value.to_string()), overflowed),
+            //E a checked overflow is still an overflow, only reported as an error instead of a value
+            Some(Err(err)) => (Err(err.to_string()), matches!(err, CalcError::Overflow { .. })),
+            None => (Err(format!("{} is not supported for {}", operation.name(), T::NAME)), false),
+        })
+        .collect())
+}
+
+// Rows written and rows whose input could not be evaluated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub rows: usize,
+    pub failed: usize,
+}
+
+// Unknown operation names are rejected before anything is read
+pub fn run(
+    input: impl BufRead,
+    output: impl Write,
+    num_type: NumType,
+    policy: OverflowPolicy,
+    names: &[String],
+) -> Result<Summary, String> {
+    dispatch!(num_type, run_typed(input, output, policy, names))
+}
+
+fn run_typed<T: Operand>(
+    input: impl BufRead,
+    output: impl Write,
+    policy: OverflowPolicy,
+    names: &[String],
+) -> Result<Summary, String> {
+    let registry = Registry::<T>::standard();
+    let operations = registry.select(names)?;
+    write_rows(input, output, policy, &operations).map_err(|err| err.to_string())
+}
+
+fn write_rows<T: Operand>(
+    input: impl BufRead,
+    mut output: impl Write,
+    policy: OverflowPolicy,
+    operations: &[&dyn Operation<T>],
+) -> io::Result<Summary> {
+    let names: Vec<&str> = operations.iter().map(|operation| operation.name()).collect();
+    writeln!(output, "line,m1,m2,{},overflowed,error", names.join(","))?;
+
+    let mut summary = Summary::default();
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(|field| field.trim().trim_matches('"'))
+            .filter(|field| !field.is_empty())
+            .collect();
+        if summary.rows == 0 && summary.failed == 0 && fields == ["m1", "m2"] {
+            continue;
+        }
+
+        let (m1, m2) = match fields.as_slice() {
+            [m1, m2] => (*m1, *m2),
+            _ => ("", ""),
+        };
+        let outcomes = if fields.len() == 2 {
+            evaluate_pair::<T>(m1, m2, operations, policy).map_err(|err| err.to_string())
+        } else {
+            Err(format!("expected 2 fields, found {}", fields.len()))
+        };
+
+        let mut row = vec![(index + 1).to_string(), m1.to_string(), m2.to_string()];
+        match outcomes {
+            Ok(outcomes) => {
+                let overflowed: Vec<&str> = operations
+                    .iter()
+                    .zip(&outcomes)
+                    .filter(|(_, (_, overflowed))| *overflowed)
+                    .map(|(operation, _)| operation.name())
+                    .collect();
+                row.extend(outcomes.into_iter().map(|(result, _)| result.unwrap_or_else(|err| err)));
+                row.push(overflowed.join(" "));
+                row.push(String::new());
+            }
+            Err(err) => {
+                summary.failed += 1;
+                row.extend(operations.iter().map(|_| String::new()));
+                row.push(String::new());
+                row.push(err);
+            }
+        }
+
+        let row: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+        writeln!(output, "{}", row.join(","))?;
+        summary.rows += 1;
+    }
+
+    output.flush()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Summary and CSV output of a batch over `input`
+    fn batch(
+        input: &str,
+        num_type: NumType,
+        policy: OverflowPolicy,
+        names: &[&str],
+    ) -> (Result<Summary, String>, String) {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let mut output = Vec::new();
+        let summary = run(input.as_bytes(), &mut output, num_type, policy, &names);
+        (summary, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn parses_operation_lists() {
+        assert_eq!(parse_names("add, mul,,shl "), ["add", "mul", "shl"]);
+        assert!(parse_names("").is_empty());
+    }
+
+    #[test]
+    fn skips_the_header_comments_and_blank_lines() {
+        let (summary, output) = batch("m1,m2\n# pairs\n\n1,2\n", NumType::I32, OverflowPolicy::Checked, &["add"]);
+        assert_eq!(summary, Ok(Summary { rows: 1, failed: 0 }));
+        assert_eq!(output, "line,m1,m2,add,overflowed,error\n4,1,2,3,,\n");
+    }
+
+    #[test]
+    fn splits_on_commas_and_whitespace() {
+        let input = "1,2\n3 4\n5, 6\n\"7\",\t8\n";
+        let (summary, output) = batch(input, NumType::I32, OverflowPolicy::Checked, &["mul"]);
+        assert_eq!(summary, Ok(Summary { rows: 4, failed: 0 }));
+        let products: Vec<&str> = output.lines().skip(1).map(|line| line.split(',').nth(3).unwrap()).collect();
+        assert_eq!(products, ["2", "12", "30", "56"]);
+    }
+
+    #[test]
+    fn keeps_going_after_a_malformed_row() {
+        let input = "1,2\n1,2,3\nx,1\n3,4\n";
+        let (summary, output) = batch(input, NumType::U8, OverflowPolicy::Checked, &["add", "sub"]);
+        assert_eq!(summary, Ok(Summary { rows: 4, failed: 2 }));
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[2], "2,,,,,,\"expected 2 fields, found 3\"");
+        assert_eq!(lines[3], "3,x,1,,,,`x` is not a valid u8");
+        assert_eq!(lines[4], "4,3,4,7,Subtraction overflow,sub,");
+    }
+
+    #[test]
+    fn flags_overflows_under_every_policy() {
+        for policy in OverflowPolicy::ALL {
+            let (_, output) = batch("127,1\n", NumType::I8, policy, &["add", "div"]);
+            let row = output.lines().nth(1).unwrap();
+            assert!(row.ends_with(",add,"), "{}: {}", policy, row);
+        }
+        let (_, output) = batch("1,0\n", NumType::I8, OverflowPolicy::Checked, &["div"]);
+        assert_eq!(output.lines().nth(1), Some("1,1,0,Division by zero,,"));
+    }
+
+    #[test]
+    fn rejects_unknown_operations_before_reading() {
+        let (summary, output) = batch("1,2\n", NumType::I32, OverflowPolicy::Checked, &["add", "frobnicate"]);
+        assert!(summary.unwrap_err().starts_with("unknown operation `frobnicate`, expected one of add,"));
+        assert!(output.is_empty());
+    }
+}
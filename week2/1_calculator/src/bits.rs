@@ -0,0 +1,107 @@
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+/** BITS
+ * Bit-level primitives of an integer type
+ * Shift and rotate amounts are u32 and always below BITS: the Calculator checks the range
+ * first, so these never panic and never silently mask the amount like `wrapping_shl` does
+ */
+pub trait Bits:
+    Copy + PartialEq + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self>
+{
+    const BITS: u32;
+    const SIGNED: bool;
+    const ZERO: Self;
+
+    // The value as a bit index / shift amount, None when negative or larger than u32::MAX
+    fn to_bit_index(self) -> Option<u32>;
+    fn from_count(count: u32) -> Self;
+
+    fn shl(self, amount: u32) -> Self;
+    // Zero-filling shift, regardless of signedness
+    fn logical_shr(self, amount: u32) -> Self;
+    // Sign-extending shift, the top bit is the sign even for unsigned types
+    fn arithmetic_shr(self, amount: u32) -> Self;
+    fn rotate_left(self, amount: u32) -> Self;
+    fn rotate_right(self, amount: u32) -> Self;
+
+    fn count_ones(self) -> u32;
+    fn leading_zeros(self) -> u32;
+    fn trailing_zeros(self) -> u32;
+
+    // `len` low bits set, len <= BITS
+    fn low_mask(len: u32) -> Self;
+}
+
+// Each integer type is paired with its same-width unsigned and signed types
+macro_rules! impl_bits {
+    ($($t:ty => $unsigned:ty, $signed:ty);* $(;)?) => {
+        $(
+            impl Bits for $t {
+                const BITS: u32 = <$t>::BITS;
+                const SIGNED: bool = <$t>::MIN != 0;
+                const ZERO: Self = 0;
+
+                fn to_bit_index(self) -> Option<u32> {
+                    u32::try_from(self).ok()
+                }
+
+                fn from_count(count: u32) -> Self {
+                    count as $t
+                }
+
+                fn shl(self, amount: u32) -> Self {
+                    self << amount
+                }
+
+                fn logical_shr(self, amount: u32) -> Self {
+                    ((self as $unsigned) >> amount) as $t
+                }
+
+                fn arithmetic_shr(self, amount: u32) -> Self {
+                    ((self as $signed) >> amount) as $t
+                }
+
+                fn rotate_left(self, amount: u32) -> Self {
+                    <$t>::rotate_left(self, amount)
+                }
+
+                fn rotate_right(self, amount: u32) -> Self {
+                    <$t>::rotate_right(self, amount)
+                }
+
+                fn count_ones(self) -> u32 {
+                    <$t>::count_ones(self)
+                }
+
+                fn leading_zeros(self) -> u32 {
+                    <$t>::leading_zeros(self)
+                }
+
+                fn trailing_zeros(self) -> u32 {
+                    <$t>::trailing_zeros(self)
+                }
+
+                fn low_mask(len: u32) -> Self {
+                    if len >= <$t>::BITS {
+                        !0
+                    } else {
+                        (((1 as $unsigned) << len) - 1) as $t
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_bits!(
+    i8 => u8, i8;
+    i16 => u16, i16;
+    i32 => u32, i32;
+    i64 => u64, i64;
+    i128 => u128, i128;
+    u8 => u8, i8;
+    u16 => u16, i16;
+    u32 => u32, i32;
+    u64 => u64, i64;
+    u128 => u128, i128;
+);
@@ -0,0 +1,949 @@
+use std::fmt;
+use crate::arithmetic::{CalcError, ExtendedArithmetic, OverflowArithmetic, OverflowPolicy};
+use crate::bits::Bits;
+use crate::eval::Operand;
+use crate::registry::{apply, Registry};
+
+// Calculator is generic over the operand type so the same operations can be
+// compared across widths (i8..i128, u8..u128) and floats (f32, f64)
+#[derive(Debug)]
+pub struct Calculator<T> {
+    pub m1: T,
+    pub m2: T,
+}
+
+// AdditiveOperations trait for addition and subtraction
+// add / subtract are checked: an overflow is returned as CalcError::Overflow instead of panicking
+pub trait AdditiveOperations<T> {
+    fn add(&self) -> Result<T, CalcError>;
+    fn wrapping_add(&self) -> T;
+    fn saturating_add(&self) -> T;
+    fn overflowing_add(&self) -> (T, bool);
+
+    fn subtract(&self) -> Result<T, CalcError>;
+    fn wrapping_subtract(&self) -> T;
+    fn saturating_subtract(&self) -> T;
+    fn overflowing_subtract(&self) -> (T, bool);
+
+    // Result under the selected policy, flagged when the policy had to absorb an overflow
+    fn add_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        select_policy(policy, self.add(), self.wrapping_add(), self.saturating_add(), self.overflowing_add())
+    }
+
+    fn subtract_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        select_policy(
+            policy,
+            self.subtract(),
+            self.wrapping_subtract(),
+            self.saturating_subtract(),
+            self.overflowing_subtract(),
+        )
+    }
+}
+
+// MultiplicativeOperations trait for multiplication and division
+// division by zero is an error under every policy
+pub trait MultiplicativeOperations<T> {
+    fn multiply(&self) -> Result<T, CalcError>;
+    fn wrapping_multiply(&self) -> T;
+    fn saturating_multiply(&self) -> T;
+    fn overflowing_multiply(&self) -> (T, bool);
+
+    fn divide(&self) -> Result<T, CalcError>;
+    fn wrapping_divide(&self) -> Result<T, CalcError>;
+    fn saturating_divide(&self) -> Result<T, CalcError>;
+    fn overflowing_divide(&self) -> Result<(T, bool), CalcError>;
+
+    fn multiply_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        select_policy(
+            policy,
+            self.multiply(),
+            self.wrapping_multiply(),
+            self.saturating_multiply(),
+            self.overflowing_multiply(),
+        )
+    }
+
+    fn divide_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        let overflowing = self.overflowing_divide()?;
+        select_policy(
+            policy,
+            self.divide(),
+            self.wrapping_divide()?,
+            self.saturating_divide()?,
+            overflowing,
+        )
+    }
+}
+
+fn select_policy<T>(
+    policy: OverflowPolicy,
+    checked: Result<T, CalcError>,
+    wrapping: T,
+    saturating: T,
+    (overflowing, overflowed): (T, bool),
+) -> Result<(T, bool), CalcError> {
+    match policy {
+        OverflowPolicy::Checked => checked.map(|value| (value, false)),
+        OverflowPolicy::Wrapping => Ok((wrapping, overflowed)),
+        OverflowPolicy::Saturating => Ok((saturating, overflowed)),
+        OverflowPolicy::Overflowing => Ok((overflowing, overflowed)),
+    }
+}
+
+// BinaryOperations trait for AND, OR, XOR, shifts, rotations and bit inspection
+// m2 is the shift / rotate amount; NOT and the bit counts only look at m1
+pub trait BinaryOperations<T> {
+    fn bitwise_and(&self) -> T;
+    fn bitwise_or(&self) -> T;
+    fn bitwise_xor(&self) -> T;
+    fn bitwise_not(&self) -> T;
+
+    fn shift_left(&self) -> Result<T, CalcError>;
+    fn shift_right_logical(&self) -> Result<T, CalcError>;
+    fn shift_right_arithmetic(&self) -> Result<T, CalcError>;
+    fn rotate_left(&self) -> Result<T, CalcError>;
+    fn rotate_right(&self) -> Result<T, CalcError>;
+
+    fn count_ones(&self) -> u32;
+    fn leading_zeros(&self) -> u32;
+    fn trailing_zeros(&self) -> u32;
+
+    // Bits [start, start + len) of m1, shifted down to bit 0
+    fn extract_bits(&self, start: u32, len: u32) -> Result<T, CalcError>;
+    // m1 with bits [start, start + len) replaced by m2
+    fn insert_bits(&self, start: u32, len: u32) -> Result<T, CalcError>;
+}
+
+impl<T: OverflowArithmetic> AdditiveOperations<T> for Calculator<T> {
+    fn add(&self) -> Result<T, CalcError> {
+        self.m1
+            .checked_add(self.m2)
+            .ok_or(CalcError::Overflow { operation: "Addition" })
+    }
+
+    fn wrapping_add(&self) -> T {
+        self.m1.wrapping_add(self.m2)
+    }
+
+    fn saturating_add(&self) -> T {
+        self.m1.saturating_add(self.m2)
+    }
+
+    fn overflowing_add(&self) -> (T, bool) {
+        self.m1.overflowing_add(self.m2)
+    }
+
+    fn subtract(&self) -> Result<T, CalcError> {
+        self.m1
+            .checked_sub(self.m2)
+            .ok_or(CalcError::Overflow { operation: "Subtraction" })
+    }
+
+    fn wrapping_subtract(&self) -> T {
+        self.m1.wrapping_sub(self.m2)
+    }
+
+    fn saturating_subtract(&self) -> T {
+        self.m1.saturating_sub(self.m2)
+    }
+
+    fn overflowing_subtract(&self) -> (T, bool) {
+        self.m1.overflowing_sub(self.m2)
+    }
+}
+
+impl<T: OverflowArithmetic> MultiplicativeOperations<T> for Calculator<T> {
+    fn multiply(&self) -> Result<T, CalcError> {
+        self.m1
+            .checked_mul(self.m2)
+            .ok_or(CalcError::Overflow { operation: "Multiplication" })
+    }
+
+    fn wrapping_multiply(&self) -> T {
+        self.m1.wrapping_mul(self.m2)
+    }
+
+    fn saturating_multiply(&self) -> T {
+        self.m1.saturating_mul(self.m2)
+    }
+
+    fn overflowing_multiply(&self) -> (T, bool) {
+        self.m1.overflowing_mul(self.m2)
+    }
+
+    fn divide(&self) -> Result<T, CalcError> {
+        self.non_zero_divisor()?;
+        self.m1
+            .checked_div(self.m2)
+            .ok_or(CalcError::Overflow { operation: "Division" })
+    }
+
+    fn wrapping_divide(&self) -> Result<T, CalcError> {
+        self.non_zero_divisor()?;
+        Ok(self.m1.wrapping_div(self.m2))
+    }
+
+    fn saturating_divide(&self) -> Result<T, CalcError> {
+        self.non_zero_divisor()?;
+        Ok(self.m1.saturating_div(self.m2))
+    }
+
+    fn overflowing_divide(&self) -> Result<(T, bool), CalcError> {
+        self.non_zero_divisor()?;
+        Ok(self.m1.overflowing_div(self.m2))
+    }
+}
+
+impl<T: OverflowArithmetic> Calculator<T> {
+    fn non_zero_divisor(&self) -> Result<(), CalcError> {
+        if self.m2.is_zero() {
+            Err(CalcError::DivisionByZero)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// UnaryOperations trait for the operations on m1 alone
+// negate / abs follow the overflow policies, sqrt and the logarithms fail outside of their domain
+pub trait UnaryOperations<T> {
+    fn negate(&self) -> Result<T, CalcError>;
+    fn wrapping_negate(&self) -> T;
+    fn saturating_negate(&self) -> T;
+    fn overflowing_negate(&self) -> (T, bool);
+
+    fn abs(&self) -> Result<T, CalcError>;
+    fn wrapping_abs(&self) -> T;
+    fn saturating_abs(&self) -> T;
+    fn overflowing_abs(&self) -> (T, bool);
+
+    fn sqrt(&self) -> Result<T, CalcError>;
+    fn log2(&self) -> Result<T, CalcError>;
+    fn log10(&self) -> Result<T, CalcError>;
+
+    fn negate_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        select_policy(
+            policy,
+            self.negate(),
+            self.wrapping_negate(),
+            self.saturating_negate(),
+            self.overflowing_negate(),
+        )
+    }
+
+    fn abs_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        select_policy(policy, self.abs(), self.wrapping_abs(), self.saturating_abs(), self.overflowing_abs())
+    }
+}
+
+// PowerOperations trait for m1 raised to the power m2
+// an integer exponent must be a u32, anything else is CalcError::InvalidExponent under every policy
+pub trait PowerOperations<T> {
+    fn power(&self) -> Result<T, CalcError>;
+    fn wrapping_power(&self) -> Result<T, CalcError>;
+    fn saturating_power(&self) -> Result<T, CalcError>;
+    fn overflowing_power(&self) -> Result<(T, bool), CalcError>;
+
+    fn power_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        let overflowing = self.overflowing_power()?;
+        select_policy(policy, self.power(), self.wrapping_power()?, self.saturating_power()?, overflowing)
+    }
+}
+
+impl<T: ExtendedArithmetic> UnaryOperations<T> for Calculator<T> {
+    fn negate(&self) -> Result<T, CalcError> {
+        self.m1.checked_neg().ok_or(CalcError::Overflow { operation: "Negation" })
+    }
+
+    fn wrapping_negate(&self) -> T {
+        self.m1.wrapping_neg()
+    }
+
+    fn saturating_negate(&self) -> T {
+        self.m1.saturating_neg()
+    }
+
+    fn overflowing_negate(&self) -> (T, bool) {
+        self.m1.overflowing_neg()
+    }
+
+    fn abs(&self) -> Result<T, CalcError> {
+        self.m1.checked_abs().ok_or(CalcError::Overflow { operation: "Absolute value" })
+    }
+
+    fn wrapping_abs(&self) -> T {
+        self.m1.wrapping_abs()
+    }
+
+    fn saturating_abs(&self) -> T {
+        self.m1.saturating_abs()
+    }
+
+    fn overflowing_abs(&self) -> (T, bool) {
+        self.m1.overflowing_abs()
+    }
+
+    fn sqrt(&self) -> Result<T, CalcError> {
+        self.m1.sqrt().ok_or(CalcError::Domain { operation: "Square root" })
+    }
+
+    fn log2(&self) -> Result<T, CalcError> {
+        self.m1.log2().ok_or(CalcError::Domain { operation: "Log2" })
+    }
+
+    fn log10(&self) -> Result<T, CalcError> {
+        self.m1.log10().ok_or(CalcError::Domain { operation: "Log10" })
+    }
+}
+
+impl<T: ExtendedArithmetic> PowerOperations<T> for Calculator<T> {
+    fn power(&self) -> Result<T, CalcError> {
+        self.m1
+            .checked_pow(self.exponent()?)
+            .ok_or(CalcError::Overflow { operation: "Power" })
+    }
+
+    fn wrapping_power(&self) -> Result<T, CalcError> {
+        Ok(self.m1.wrapping_pow(self.exponent()?))
+    }
+
+    fn saturating_power(&self) -> Result<T, CalcError> {
+        Ok(self.m1.saturating_pow(self.exponent()?))
+    }
+
+    fn overflowing_power(&self) -> Result<(T, bool), CalcError> {
+        Ok(self.m1.overflowing_pow(self.exponent()?))
+    }
+}
+
+impl<T: ExtendedArithmetic> Calculator<T> {
+    fn exponent(&self) -> Result<T::Exponent, CalcError> {
+        self.m2.exponent().ok_or(CalcError::InvalidExponent)
+    }
+}
+
+/** REDUCTIONS
+ * n-ary operations over a slice of values, e.g. the rewards accumulated by a loop
+ * `sum` / `product` fold left to right with the Calculator under the selected policy, so a
+ * saturating sum clamps at the first overflow and a wrapping one may come back in range:
+ * the flag tells whether any step overflowed. `mean` is the sum divided by the count
+ */
+pub trait ReductionOperations<T> {
+    fn sum_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError>;
+    fn product_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError>;
+    fn mean_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError>;
+    fn minimum(&self) -> Result<T, CalcError>;
+    fn maximum(&self) -> Result<T, CalcError>;
+}
+
+impl<T: ExtendedArithmetic> ReductionOperations<T> for [T] {
+    fn sum_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        let (&first, rest) = self.split_first().ok_or(CalcError::Empty { operation: "Sum" })?;
+        rest.iter().try_fold((first, false), |(total, overflowed), &value| {
+            let (total, step) = Calculator { m1: total, m2: value }.add_with(policy)?;
+            Ok((total, overflowed || step))
+        })
+    }
+
+    fn product_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        let (&first, rest) = self.split_first().ok_or(CalcError::Empty { operation: "Product" })?;
+        rest.iter().try_fold((first, false), |(total, overflowed), &value| {
+            let (total, step) = Calculator { m1: total, m2: value }.multiply_with(policy)?;
+            Ok((total, overflowed || step))
+        })
+    }
+
+    fn mean_with(&self, policy: OverflowPolicy) -> Result<(T, bool), CalcError> {
+        let (sum, overflowed) = self.sum_with(policy).map_err(|err| match err {
+            CalcError::Empty { .. } => CalcError::Empty { operation: "Mean" },
+            err => err,
+        })?;
+        let count = T::from_len(self.len()).ok_or(CalcError::Overflow { operation: "Mean" })?;
+        let (mean, step) = Calculator { m1: sum, m2: count }.divide_with(policy)?;
+        Ok((mean, overflowed || step))
+    }
+
+    //E NaN compares false both ways, so a float NaN is skipped unless it comes first
+    fn minimum(&self) -> Result<T, CalcError> {
+        let (&first, rest) = self.split_first().ok_or(CalcError::Empty { operation: "Min" })?;
+        Ok(rest.iter().fold(first, |min, &value| if value < min { value } else { min }))
+    }
+
+    fn maximum(&self) -> Result<T, CalcError> {
+        let (&first, rest) = self.split_first().ok_or(CalcError::Empty { operation: "Max" })?;
+        Ok(rest.iter().fold(first, |max, &value| if value > max { value } else { max }))
+    }
+}
+
+//E only integer types implement Bits, so floats never get BinaryOperations
+impl<T: Bits> BinaryOperations<T> for Calculator<T> {
+    fn bitwise_and(&self) -> T {
+        self.m1 & self.m2
+    }
+
+    fn bitwise_or(&self) -> T {
+        self.m1 | self.m2
+    }
+
+    fn bitwise_xor(&self) -> T {
+        self.m1 ^ self.m2
+    }
+
+    fn bitwise_not(&self) -> T {
+        !self.m1
+    }
+
+    fn shift_left(&self) -> Result<T, CalcError> {
+        Ok(self.m1.shl(self.shift_amount()?))
+    }
+
+    fn shift_right_logical(&self) -> Result<T, CalcError> {
+        Ok(self.m1.logical_shr(self.shift_amount()?))
+    }
+
+    fn shift_right_arithmetic(&self) -> Result<T, CalcError> {
+        Ok(self.m1.arithmetic_shr(self.shift_amount()?))
+    }
+
+    fn rotate_left(&self) -> Result<T, CalcError> {
+        Ok(self.m1.rotate_left(self.shift_amount()?))
+    }
+
+    fn rotate_right(&self) -> Result<T, CalcError> {
+        Ok(self.m1.rotate_right(self.shift_amount()?))
+    }
+
+    fn count_ones(&self) -> u32 {
+        self.m1.count_ones()
+    }
+
+    fn leading_zeros(&self) -> u32 {
+        self.m1.leading_zeros()
+    }
+
+    fn trailing_zeros(&self) -> u32 {
+        self.m1.trailing_zeros()
+    }
+
+    fn extract_bits(&self, start: u32, len: u32) -> Result<T, CalcError> {
+        check_bit_range::<T>(start, len)?;
+        if len == 0 {
+            return Ok(T::ZERO);
+        }
+        Ok(self.m1.logical_shr(start) & T::low_mask(len))
+    }
+
+    fn insert_bits(&self, start: u32, len: u32) -> Result<T, CalcError> {
+        check_bit_range::<T>(start, len)?;
+        let mask = T::low_mask(len);
+        if self.m2 & !mask != T::ZERO {
+            return Err(CalcError::FieldTooWide { len });
+        }
+        if len == 0 {
+            return Ok(self.m1);
+        }
+        Ok((self.m1 & !mask.shl(start)) | self.m2.shl(start))
+    }
+}
+
+impl<T: Bits> Calculator<T> {
+    //E Rust panics (debug) or masks the amount (release) when shifting by >= BITS, we report it instead
+    fn shift_amount(&self) -> Result<u32, CalcError> {
+        match self.m2.to_bit_index() {
+            Some(amount) if amount < T::BITS => Ok(amount),
+            _ => Err(CalcError::ShiftOutOfRange { bits: T::BITS }),
+        }
+    }
+}
+
+fn check_bit_range<T: Bits>(start: u32, len: u32) -> Result<(), CalcError> {
+    match start.checked_add(len) {
+        Some(end) if end <= T::BITS && (len == 0 || start < T::BITS) => Ok(()),
+        _ => Err(CalcError::BitRangeOutOfRange { start, len, bits: T::BITS }),
+    }
+}
+
+// One row per policy-aware operation, one column per overflow policy (see OverflowPolicy::ALL)
+pub fn policy_rows<T: Operand>(calc: &Calculator<T>, registry: &Registry<T>) -> Vec<(&'static str, [String; 4])> {
+    fn shown<T: fmt::Display>(policy: OverflowPolicy, result: Result<(T, bool), CalcError>) -> String {
+        match result {
+            Ok((value, overflowed)) if policy == OverflowPolicy::Overflowing => {
+                format!("{} ({})", value, if overflowed { "overflow" } else { "ok" })
+            }
+            Ok((value, _)) => value.to_string(),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    registry
+        .iter()
+        .filter(|operation| operation.uses_policy())
+        .filter_map(|operation| {
+            let results = OverflowPolicy::ALL.map(|policy| apply(operation, calc, policy).map(|result| shown(policy, result)));
+            //E an operation the type doesn't support has no row at all
+            let [Some(checked), Some(wrapping), Some(saturating), Some(overflowing)] = results else {
+                return None;
+            };
+            Some((operation.label(), [checked, wrapping, saturating, overflowing]))
+        })
+        .collect()
+}
+
+// Writes the policy rows side by side, each column as wide as its longest cell
+pub fn write_policy_table(f: &mut impl fmt::Write, rows: &[(&'static str, [String; 4])]) -> fmt::Result {
+    let headers = OverflowPolicy::ALL.map(|policy| policy.to_string());
+    let mut widths = headers.clone().map(|header| header.len());
+    for (_, cells) in rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut line = format!("{:<16}", "Operation");
+    for (header, width) in headers.iter().zip(widths) {
+        line += &format!("{:<w$}", header, w = width + 2);
+    }
+    write!(f, "{}", line.trim_end())?;
+
+    for (name, cells) in rows {
+        let mut line = format!("{:<16}", name);
+        for (cell, width) in cells.iter().zip(widths) {
+            line += &format!("{:<w$}", cell, w = width + 2);
+        }
+        write!(f, "\n{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+// The other supported operations, one `label: result` line each; an out of range shift amount is printed as its error
+pub fn write_single_operations<T: Operand>(
+    f: &mut impl fmt::Write,
+    calc: &Calculator<T>,
+    registry: &Registry<T>,
+) -> fmt::Result {
+    for operation in registry.iter().filter(|operation| !operation.uses_policy()) {
+        match apply(operation, calc, OverflowPolicy::Checked) {
+            Some(Ok((value, _))) => write!(f, "\n{}: {}", operation.label(), value)?,
+            Some(Err(err)) => write!(f, "\n{}: {}", operation.label(), err)?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+// Every registered operation: the policy table, then the operations without a policy
+impl<T: Operand> fmt::Display for Calculator<T> {
+
+    //E required method for implementing the Display trait in Rust
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        let registry = Registry::standard();
+        writeln!(f, "Calculator<{}>({}, {}):", T::NAME, self.m1, self.m2)?;
+        write_policy_table(f, &policy_rows(self, &registry))?;
+        write_single_operations(f, self, &registry)
+    }
+}
+
+
+pub fn print_output<T: Operand>(calc: &Calculator<T>) {
+
+    let registry = Registry::standard();
+    let mut table = String::new();
+    write_policy_table(&mut table, &policy_rows(calc, &registry)).expect("writing to a String never fails");
+    println!("{}", table);
+
+    let mut others = String::new();
+    write_single_operations(&mut others, calc, &registry).expect("writing to a String never fails");
+    println!("{}", others.trim_start());
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::u256::U256;
+    use proptest::prelude::*;
+
+    fn calc<T>(m1: T, m2: T) -> Calculator<T> {
+        Calculator { m1, m2 }
+    }
+
+    // Every policy agrees with the others: checked fails exactly when overflowing flags,
+    // wrapping is the overflowing value and saturating only differs on overflow
+    fn assert_policies_agree<T>(checked: Result<T, CalcError>, wrapping: T, saturating: T, overflowing: (T, bool))
+    where
+        T: OverflowArithmetic + PartialEq + fmt::Debug,
+    {
+        let (value, overflowed) = overflowing;
+        assert_eq!(wrapping, value);
+        match checked {
+            Ok(checked) => {
+                assert!(!overflowed);
+                assert_eq!(checked, value);
+                assert_eq!(saturating, value);
+            }
+            Err(err) => {
+                assert!(overflowed);
+                assert!(matches!(err, CalcError::Overflow { .. }));
+            }
+        }
+    }
+
+    // Properties and boundary values shared by every primitive integer type
+    macro_rules! integer_properties {
+        ($($module:ident: $t:ty),* $(,)?) => {
+            $(
+                mod $module {
+                    use super::*;
+
+                    const BOUNDARIES: [$t; 7] = [
+                        <$t>::MIN,
+                        <$t>::MIN.wrapping_add(1),
+                        0,
+                        1,
+                        (0 as $t).wrapping_sub(1),
+                        <$t>::MAX - 1,
+                        <$t>::MAX,
+                    ];
+
+                    fn assert_all_policies(a: $t, b: $t) {
+                        let c = calc(a, b);
+                        assert_policies_agree(c.add(), c.wrapping_add(), c.saturating_add(), c.overflowing_add());
+                        assert_policies_agree(
+                            c.subtract(),
+                            c.wrapping_subtract(),
+                            c.saturating_subtract(),
+                            c.overflowing_subtract(),
+                        );
+                        assert_policies_agree(
+                            c.multiply(),
+                            c.wrapping_multiply(),
+                            c.saturating_multiply(),
+                            c.overflowing_multiply(),
+                        );
+                        assert_policies_agree(c.negate(), c.wrapping_negate(), c.saturating_negate(), c.overflowing_negate());
+                        assert_policies_agree(c.abs(), c.wrapping_abs(), c.saturating_abs(), c.overflowing_abs());
+                        if b == 0 {
+                            for policy in OverflowPolicy::ALL {
+                                assert_eq!(c.divide_with(policy), Err(CalcError::DivisionByZero));
+                            }
+                        } else {
+                            assert_policies_agree(
+                                c.divide(),
+                                c.wrapping_divide().unwrap(),
+                                c.saturating_divide().unwrap(),
+                                c.overflowing_divide().unwrap(),
+                            );
+                        }
+                    }
+
+                    #[test]
+                    fn boundary_pairs_agree_across_policies() {
+                        for a in BOUNDARIES {
+                            for b in BOUNDARIES {
+                                assert_all_policies(a, b);
+                            }
+                        }
+                    }
+
+                    #[test]
+                    fn max_plus_one() {
+                        let c = calc(<$t>::MAX, 1);
+                        assert_eq!(c.add(), Err(CalcError::Overflow { operation: "Addition" }));
+                        assert_eq!(c.add_with(OverflowPolicy::Wrapping), Ok((<$t>::MIN, true)));
+                        assert_eq!(c.add_with(OverflowPolicy::Saturating), Ok((<$t>::MAX, true)));
+                        assert_eq!(c.add_with(OverflowPolicy::Overflowing), Ok((<$t>::MIN, true)));
+                    }
+
+                    #[test]
+                    fn min_minus_one() {
+                        let c = calc(<$t>::MIN, 1);
+                        assert_eq!(c.subtract(), Err(CalcError::Overflow { operation: "Subtraction" }));
+                        assert_eq!(c.subtract_with(OverflowPolicy::Wrapping), Ok((<$t>::MAX, true)));
+                        assert_eq!(c.subtract_with(OverflowPolicy::Saturating), Ok((<$t>::MIN, true)));
+                    }
+
+                    #[test]
+                    fn shifts_reject_the_full_width() {
+                        let bits = <$t>::BITS as $t;
+                        assert_eq!(calc(1, bits).shift_left(), Err(CalcError::ShiftOutOfRange { bits: <$t>::BITS }));
+                        assert_eq!(calc(1, bits - 1).shift_left(), Ok((1 as $t) << (bits - 1)));
+                        assert!(calc(1, (0 as $t).wrapping_sub(1)).rotate_left().is_err());
+                    }
+
+                    proptest! {
+                        #[test]
+                        fn power_is_repeated_multiplication(a: $t, e in 1u8..8) {
+                            let c = calc(a, e as $t);
+                            let factors = vec![a; e as usize];
+                            assert_policies_agree(
+                                c.power(),
+                                c.wrapping_power().unwrap(),
+                                c.saturating_power().unwrap(),
+                                c.overflowing_power().unwrap(),
+                            );
+                            prop_assert_eq!(c.power().ok(), factors.product_with(OverflowPolicy::Checked).ok().map(|(value, _)| value));
+                            prop_assert_eq!(c.wrapping_power().unwrap(), factors.product_with(OverflowPolicy::Wrapping).unwrap().0);
+                        }
+
+                        #[test]
+                        fn sqrt_is_the_integer_root(a: $t) {
+                            match calc(a, 0).sqrt() {
+                                Ok(root) => {
+                                    prop_assert!(calc(root, root).multiply().unwrap() <= a);
+                                    prop_assert!(calc(root + 1, root + 1).multiply().map_or(true, |square| square > a));
+                                }
+                                Err(err) => {
+                                    prop_assert!(a < 0 as $t);
+                                    prop_assert_eq!(err, CalcError::Domain { operation: "Square root" });
+                                }
+                            }
+                        }
+
+                        #[test]
+                        fn add_and_multiply_commute(a: $t, b: $t) {
+                            for policy in OverflowPolicy::ALL {
+                                prop_assert_eq!(calc(a, b).add_with(policy), calc(b, a).add_with(policy));
+                                prop_assert_eq!(calc(a, b).multiply_with(policy), calc(b, a).multiply_with(policy));
+                            }
+                        }
+
+                        #[test]
+                        fn bitwise_operations_commute(a: $t, b: $t) {
+                            prop_assert_eq!(calc(a, b).bitwise_and(), calc(b, a).bitwise_and());
+                            prop_assert_eq!(calc(a, b).bitwise_or(), calc(b, a).bitwise_or());
+                            prop_assert_eq!(calc(a, b).bitwise_xor(), calc(b, a).bitwise_xor());
+                        }
+
+                        #[test]
+                        fn xor_is_its_own_inverse(a: $t, b: $t) {
+                            let once = calc(a, b).bitwise_xor();
+                            prop_assert_eq!(calc(once, b).bitwise_xor(), a);
+                        }
+
+                        #[test]
+                        fn quotient_times_divisor_plus_remainder(a: $t, b: $t) {
+                            prop_assume!(b != 0);
+                            //E MIN / -1 is the one overflowing division, wrapping keeps the identity anyway
+                            let (quotient, _) = calc(a, b).divide_with(OverflowPolicy::Wrapping).unwrap();
+                            prop_assert_eq!(quotient.wrapping_mul(b).wrapping_add(a.wrapping_rem(b)), a);
+                        }
+
+                        #[test]
+                        fn policies_agree(a: $t, b: $t) {
+                            assert_all_policies(a, b);
+                        }
+
+                        #[test]
+                        fn subtract_undoes_add(a: $t, b: $t) {
+                            let (sum, _) = calc(a, b).add_with(OverflowPolicy::Wrapping).unwrap();
+                            prop_assert_eq!(calc(sum, b).wrapping_subtract(), a);
+                        }
+
+                        #[test]
+                        fn rotations_are_inverse(a: $t, n in 0..<$t>::BITS) {
+                            let rotated = calc(a, n as $t).rotate_left().unwrap();
+                            prop_assert_eq!(calc(rotated, n as $t).rotate_right().unwrap(), a);
+                        }
+
+                        #[test]
+                        fn insert_then_extract(a: $t, field: $t, start in 0..<$t>::BITS, len in 1..=<$t>::BITS) {
+                            prop_assume!(start + len <= <$t>::BITS);
+                            let mask = <$t as Bits>::low_mask(len);
+                            let inserted = calc(a, field & mask).insert_bits(start, len).unwrap();
+                            prop_assert_eq!(calc(inserted, 0).extract_bits(start, len).unwrap(), field & mask);
+                        }
+                    }
+                }
+            )*
+        };
+    }
+
+    integer_properties!(
+        i8_properties: i8,
+        i16_properties: i16,
+        i32_properties: i32,
+        i64_properties: i64,
+        i128_properties: i128,
+        u8_properties: u8,
+        u16_properties: u16,
+        u32_properties: u32,
+        u64_properties: u64,
+        u128_properties: u128,
+    );
+
+    // The exact result fits in an i128 (u64 * u64 does not), so every policy can be checked against it
+    macro_rules! exact_policy_results {
+        ($($name:ident: $t:ty),* $(,)?) => {
+            $(
+                proptest! {
+                    #[test]
+                    fn $name(a: $t, b: $t) {
+                        let exact = [
+                            a as i128 + b as i128,
+                            a as i128 - b as i128,
+                            a as i128 * b as i128,
+                        ];
+                        let c = calc(a, b);
+                        let results = [
+                            (c.add(), c.wrapping_add(), c.saturating_add()),
+                            (c.subtract(), c.wrapping_subtract(), c.saturating_subtract()),
+                            (c.multiply(), c.wrapping_multiply(), c.saturating_multiply()),
+                        ];
+                        for (exact, (checked, wrapping, saturating)) in exact.into_iter().zip(results) {
+                            let fits = (<$t>::MIN as i128..=<$t>::MAX as i128).contains(&exact);
+                            prop_assert_eq!(checked.ok(), fits.then_some(exact as $t));
+                            prop_assert_eq!(wrapping, exact as $t);
+                            prop_assert_eq!(saturating, exact.clamp(<$t>::MIN as i128, <$t>::MAX as i128) as $t);
+                        }
+                    }
+                }
+            )*
+        };
+    }
+
+    exact_policy_results!(
+        i8_exact: i8,
+        i16_exact: i16,
+        i32_exact: i32,
+        i64_exact: i64,
+        u8_exact: u8,
+        u16_exact: u16,
+        u32_exact: u32,
+    );
+
+    #[test]
+    fn signed_min_divided_by_minus_one() {
+        let c = calc(i8::MIN, -1);
+        assert_eq!(c.divide(), Err(CalcError::Overflow { operation: "Division" }));
+        assert_eq!(c.divide_with(OverflowPolicy::Wrapping), Ok((i8::MIN, true)));
+        assert_eq!(c.divide_with(OverflowPolicy::Saturating), Ok((i8::MAX, true)));
+        assert_eq!(c.divide_with(OverflowPolicy::Overflowing), Ok((i8::MIN, true)));
+    }
+
+    #[test]
+    fn float_overflow_is_reported() {
+        let c = calc(f64::MAX, 2.0);
+        assert!(c.multiply().is_err());
+        assert_eq!(c.multiply_with(OverflowPolicy::Wrapping), Ok((f64::INFINITY, true)));
+        assert_eq!(c.multiply_with(OverflowPolicy::Saturating), Ok((f64::MAX, true)));
+        assert_eq!(calc(1.0, 0.0).divide(), Err(CalcError::DivisionByZero));
+    }
+
+    #[test]
+    fn negate_and_abs_of_min() {
+        let c = calc(i8::MIN, 0);
+        assert_eq!(c.negate(), Err(CalcError::Overflow { operation: "Negation" }));
+        assert_eq!(c.negate_with(OverflowPolicy::Wrapping), Ok((i8::MIN, true)));
+        assert_eq!(c.abs_with(OverflowPolicy::Saturating), Ok((i8::MAX, true)));
+        assert_eq!(calc(5u8, 0).negate_with(OverflowPolicy::Saturating), Ok((0, true)));
+        assert_eq!(calc(-2.5f64, 0.0).abs(), Ok(2.5));
+    }
+
+    #[test]
+    fn power_overflow_and_exponent_range() {
+        assert_eq!(calc(2i64, 62).power(), Ok(1 << 62));
+        assert_eq!(calc(2i64, 63).power(), Err(CalcError::Overflow { operation: "Power" }));
+        assert_eq!(calc(2i64, 63).power_with(OverflowPolicy::Saturating), Ok((i64::MAX, true)));
+        assert_eq!(calc(-2i64, 63).power_with(OverflowPolicy::Checked), Ok((i64::MIN, false)));
+        assert_eq!(calc(3u8, 0).power(), Ok(1));
+        for policy in OverflowPolicy::ALL {
+            assert_eq!(calc(2i32, -1).power_with(policy), Err(CalcError::InvalidExponent));
+            assert_eq!(calc(2.0f64, 0.5).power_with(policy), Ok((2f64.sqrt(), false)));
+        }
+        assert_eq!(calc(2u128, 128).power_with(OverflowPolicy::Wrapping), Ok((0, true)));
+        assert_eq!(calc(U256::from(2), U256::from(255)).power(), Ok(Bits::shl(U256::ONE, 255)));
+        assert!(calc(U256::from(2), U256::from(256)).power().is_err());
+    }
+
+    #[test]
+    fn roots_and_logarithms() {
+        assert_eq!(calc(99u32, 0).sqrt(), Ok(9));
+        assert_eq!(calc(1000i16, 0).log10(), Ok(3));
+        assert_eq!(calc(1025u64, 0).log2(), Ok(10));
+        assert_eq!(calc(0u8, 0).log2(), Err(CalcError::Domain { operation: "Log2" }));
+        assert_eq!(calc(-1.0f32, 0.0).sqrt(), Err(CalcError::Domain { operation: "Square root" }));
+        assert_eq!(calc(U256::MAX, U256::ZERO).sqrt(), Ok(U256::from(u128::MAX)));
+        assert_eq!(calc(U256::MAX, U256::ZERO).log2(), Ok(U256::from(255)));
+        assert_eq!(calc(U256::MAX, U256::ZERO).log10(), Ok(U256::from(77)));
+    }
+
+    #[test]
+    fn reductions() {
+        let values = [100i8, 50, -120];
+        assert_eq!(values.sum_with(OverflowPolicy::Checked), Err(CalcError::Overflow { operation: "Addition" }));
+        //E 100 + 50 wraps to -106, then -120 wraps back to 30 = the exact sum
+        assert_eq!(values.sum_with(OverflowPolicy::Wrapping), Ok((30, true)));
+        assert_eq!(values.sum_with(OverflowPolicy::Saturating), Ok((7, true)));
+        assert_eq!(values.product_with(OverflowPolicy::Saturating), Ok((i8::MIN, true)));
+        assert_eq!([7i8, 8, 9].mean_with(OverflowPolicy::Checked), Ok((8, false)));
+        assert_eq!(values.minimum(), Ok(-120));
+        assert_eq!(values.maximum(), Ok(100));
+        assert_eq!([4.0f64, 1.0].mean_with(OverflowPolicy::Checked), Ok((2.5, false)));
+
+        let empty: [u32; 0] = [];
+        assert_eq!(empty.sum_with(OverflowPolicy::Wrapping), Err(CalcError::Empty { operation: "Sum" }));
+        assert_eq!(empty.mean_with(OverflowPolicy::Checked), Err(CalcError::Empty { operation: "Mean" }));
+        assert_eq!(empty.maximum(), Err(CalcError::Empty { operation: "Max" }));
+        //E the count itself may not fit: 200 values are fine as i8 operands but 200 is not an i8
+        assert_eq!([0i8; 200].mean_with(OverflowPolicy::Checked), Err(CalcError::Overflow { operation: "Mean" }));
+    }
+
+    fn u256() -> impl Strategy<Value = U256> {
+        any::<(u128, u128)>().prop_map(|(high, low)| Bits::shl(U256::from(high), 128) | U256::from(low))
+    }
+
+    proptest! {
+        #[test]
+        fn float_add_and_multiply_commute(a: f64, b: f64) {
+            for policy in OverflowPolicy::ALL {
+                let (ab, ba) = (calc(a, b).add_with(policy), calc(b, a).add_with(policy));
+                prop_assert!(ab == ba || ab.is_ok_and(|(value, _)| value.is_nan()));
+                let (ab, ba) = (calc(a, b).multiply_with(policy), calc(b, a).multiply_with(policy));
+                prop_assert!(ab == ba || ab.is_ok_and(|(value, _)| value.is_nan()));
+            }
+        }
+
+        #[test]
+        fn u256_add_and_multiply_commute(a in u256(), b in u256()) {
+            for policy in OverflowPolicy::ALL {
+                prop_assert_eq!(calc(a, b).add_with(policy), calc(b, a).add_with(policy));
+                prop_assert_eq!(calc(a, b).multiply_with(policy), calc(b, a).multiply_with(policy));
+            }
+        }
+
+        #[test]
+        fn u256_quotient_times_divisor_plus_remainder(a in u256(), b in u256()) {
+            prop_assume!(b != U256::ZERO);
+            let quotient = calc(a, b).divide().unwrap();
+            let product = calc(quotient, b).multiply().unwrap();
+            prop_assert!(product <= a);
+            let remainder = calc(a, product).subtract().unwrap();
+            prop_assert!(remainder < b);
+        }
+
+        #[test]
+        fn u256_xor_is_its_own_inverse(a in u256(), b in u256()) {
+            prop_assert_eq!(calc(calc(a, b).bitwise_xor(), b).bitwise_xor(), a);
+        }
+
+        #[test]
+        fn u256_policies_agree(a in u256(), b in u256()) {
+            let c = calc(a, b);
+            assert_policies_agree(c.add(), c.wrapping_add(), c.saturating_add(), c.overflowing_add());
+            assert_policies_agree(c.subtract(), c.wrapping_subtract(), c.saturating_subtract(), c.overflowing_subtract());
+            assert_policies_agree(c.multiply(), c.wrapping_multiply(), c.saturating_multiply(), c.overflowing_multiply());
+        }
+    }
+
+    #[test]
+    fn u256_boundaries() {
+        let c = calc(U256::MAX, U256::ONE);
+        assert_eq!(c.add_with(OverflowPolicy::Wrapping), Ok((U256::ZERO, true)));
+        assert_eq!(c.add_with(OverflowPolicy::Saturating), Ok((U256::MAX, true)));
+        assert_eq!(calc(U256::ZERO, U256::ONE).subtract_with(OverflowPolicy::Saturating), Ok((U256::ZERO, true)));
+        assert_eq!(calc(U256::MAX, U256::MAX).wrapping_multiply(), U256::ONE);
+        assert_eq!(calc(U256::MAX, U256::ZERO).divide(), Err(CalcError::DivisionByZero));
+    }
+}
@@ -0,0 +1,454 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::bits::Bits;
+use crate::eval::{EvalError, Operand};
+use crate::report::{csv_field, json_string, markdown_cell, Format, Value};
+use crate::u256::U256;
+
+/** CASTS
+ * One value seen through every Rust integer type, the way cast bugs slip in:
+ * `amount as u64` on a u128, `amount.to_le_bytes()[0]`, `amount.low.into()` on a Cairo u256
+ *  - `as`: what the cast silently produces, in decimal and hex
+ *  - `try_from`: whether the checked conversion accepts the value (floats have no TryFrom)
+ *  - lost: what the `as` cast threw away, down to the dropped bits
+ *  - views: the little / big-endian bytes and the u64 / u128 limbs of the value
+ *
+ * U256 has no `as`: its casts start from the `low` limb, which is what truncating code does
+ * CSV output only holds the casts, the other formats add the views
+ */
+pub trait Castable: Operand {
+    fn casts(self) -> Vec<Cast>;
+    fn le_bytes(self) -> Vec<u8>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Loss {
+    None,
+    // every bit is kept, but read with the other signedness or sign-extended
+    Sign,
+    // the bits in `bits` were set and don't fit the target, `dropped` is their value
+    Truncated { bits: Range<u32>, dropped: String },
+    // float sources: `as` rounds toward zero, saturates at the bounds and maps NaN to 0
+    Fraction,
+    Saturated,
+    NaN,
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Loss::None => Ok(()),
+            Loss::Sign => f.write_str("sign reinterpreted"),
+            Loss::Truncated { bits, dropped } => write!(f, "bits {}..{} dropped: {}", bits.start, bits.end, dropped),
+            Loss::Fraction => f.write_str("fraction dropped"),
+            Loss::Saturated => f.write_str("saturated"),
+            Loss::NaN => f.write_str("NaN becomes 0"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cast {
+    pub target: &'static str,
+    pub bits: u32,
+    pub value: String,
+    pub hex: String,
+    // None when the target has no TryFrom for the source type
+    pub checked: Option<bool>,
+    pub lost: Loss,
+}
+
+// Expands `$cast!(args, target)` once per integer target, narrowest first
+macro_rules! for_each_target {
+    ($cast:ident!($($arg:tt)*)) => {
+        vec![
+            $cast!($($arg)*, i8),
+            $cast!($($arg)*, i16),
+            $cast!($($arg)*, i32),
+            $cast!($($arg)*, i64),
+            $cast!($($arg)*, i128),
+            $cast!($($arg)*, u8),
+            $cast!($($arg)*, u16),
+            $cast!($($arg)*, u32),
+            $cast!($($arg)*, u64),
+            $cast!($($arg)*, u128),
+        ]
+    };
+}
+
+macro_rules! integer_cast {
+    ($value:expr, $unsigned:ty, $target:ty) => {{
+        let value = $value;
+        let cast = value as $target;
+        let checked = <$target>::try_from(value).is_ok();
+        //E the bits above the target width, zero when only the sign interpretation changes
+        let (target_bits, source_bits) = (<$target>::BITS, <$unsigned>::BITS);
+        let high = (value as $unsigned).checked_shr(target_bits).unwrap_or(0);
+        let lost = if checked {
+            Loss::None
+        } else if high == 0 {
+            Loss::Sign
+        } else {
+            Loss::Truncated {
+                bits: target_bits..source_bits,
+                dropped: format!("{:#x}", high),
+            }
+        };
+        Cast {
+            target: stringify!($target),
+            bits: <$target>::BITS,
+            value: cast.to_string(),
+            hex: format!("{:#x}", cast),
+            checked: Some(checked),
+            lost,
+        }
+    }};
+}
+
+macro_rules! float_cast {
+    ($value:expr, $target:ty) => {{
+        let value = $value as f64;
+        let cast = value as $target;
+        //E the bounds are powers of two, exact in f64 unlike MAX, which rounds up to 2^63 for i64
+        let signed = <$target>::MIN != 0;
+        let (lower, upper) = (<$target>::MIN as f64, 2f64.powi((<$target>::BITS - signed as u32) as i32));
+        let lost = if value.is_nan() {
+            Loss::NaN
+        } else if value.trunc() < lower || value.trunc() >= upper {
+            Loss::Saturated
+        } else if value.fract() != 0.0 {
+            Loss::Fraction
+        } else {
+            Loss::None
+        };
+        Cast {
+            target: stringify!($target),
+            bits: <$target>::BITS,
+            value: cast.to_string(),
+            hex: format!("{:#x}", cast),
+            checked: None,
+            lost,
+        }
+    }};
+}
+
+macro_rules! impl_integer_castable {
+    ($($t:ty => $unsigned:ty),* $(,)?) => {
+        $(
+            impl Castable for $t {
+                fn casts(self) -> Vec<Cast> {
+                    for_each_target!(integer_cast!(self, $unsigned))
+                }
+
+                fn le_bytes(self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_castable!(
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128,
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128,
+);
+
+macro_rules! impl_float_castable {
+    ($($t:ty),*) => {
+        $(
+            impl Castable for $t {
+                fn casts(self) -> Vec<Cast> {
+                    for_each_target!(float_cast!(self))
+                }
+
+                fn le_bytes(self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_float_castable!(f32, f64);
+
+impl Castable for U256 {
+    fn casts(self) -> Vec<Cast> {
+        let mut casts = self.low().casts();
+        if self.high() != 0 {
+            for cast in &mut casts {
+                cast.checked = Some(false);
+                cast.lost = Loss::Truncated {
+                    bits: cast.bits..256,
+                    dropped: format!("{:#x}", Bits::logical_shr(self, cast.bits)),
+                };
+            }
+        } else {
+            //E the low limb's casts stop at bit 128, the source still spans 256
+            for cast in &mut casts {
+                if let Loss::Truncated { bits, .. } = &mut cast.lost {
+                    bits.end = 256;
+                }
+            }
+        }
+        casts
+    }
+
+    fn le_bytes(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CastReport {
+    pub type_name: &'static str,
+    pub value: Value,
+    pub casts: Vec<Cast>,
+    pub le_bytes: Vec<u8>,
+}
+
+pub fn build_cast_report<T: Castable>(literal: &str) -> Result<CastReport, EvalError> {
+    let value = T::parse_literal(literal).ok_or_else(|| EvalError::InvalidLiteral {
+        literal: literal.to_string(),
+        type_name: T::NAME,
+    })?;
+    Ok(CastReport {
+        type_name: T::NAME,
+        value: Value::of(value),
+        casts: value.casts(),
+        le_bytes: value.le_bytes(),
+    })
+}
+
+const HEADER: [&str; 5] = ["target", "as", "hex", "try_from", "lost"];
+
+fn bytes(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:#04x}", byte)).collect();
+    format!("[{}]", bytes.join(", "))
+}
+
+impl CastReport {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.to_text(),
+            Format::Json => self.to_json(),
+            Format::Csv => self.to_csv(),
+            Format::Markdown => self.to_markdown(),
+        }
+    }
+
+    fn cells(&self) -> Vec<[String; 5]> {
+        self.casts
+            .iter()
+            .map(|cast| {
+                let checked = match cast.checked {
+                    Some(true) => format!("Ok({})", cast.value),
+                    Some(false) => "Err(TryFromIntError)".to_string(),
+                    None => "-".to_string(),
+                };
+                [cast.target.to_string(), cast.value.clone(), cast.hex.clone(), checked, cast.lost.to_string()]
+            })
+            .collect()
+    }
+
+    // Byte and limb views, limbs only for values wider than the limb, least significant first
+    fn views(&self) -> Vec<(&'static str, String)> {
+        let mut big_endian = self.le_bytes.clone();
+        big_endian.reverse();
+        let mut views = vec![("to_le_bytes", bytes(&self.le_bytes)), ("to_be_bytes", bytes(&big_endian))];
+
+        if self.le_bytes.len() > 8 {
+            let limbs: Vec<String> = self
+                .le_bytes
+                .chunks(8)
+                .map(|chunk| format!("{:#x}", u64::from_le_bytes(chunk.try_into().expect("8-byte chunk"))))
+                .collect();
+            views.push(("u64 limbs", format!("[{}]", limbs.join(", "))));
+        }
+        if self.le_bytes.len() > 16 {
+            let limbs: Vec<String> = self
+                .le_bytes
+                .chunks(16)
+                .map(|chunk| format!("{:#x}", u128::from_le_bytes(chunk.try_into().expect("16-byte chunk"))))
+                .collect();
+            views.push(("u128 limbs (low, high)", format!("[{}]", limbs.join(", "))));
+        }
+        views
+    }
+
+    fn title(&self) -> String {
+        format!("{} {} = {}", self.type_name, self.value.decimal, self.value.hex)
+    }
+
+    fn to_text(&self) -> String {
+        let cells = self.cells();
+        let mut widths = HEADER.map(str::len);
+        for row in &cells {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let line = |row: [&str; 5]| {
+            let padded: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+
+        let mut out = self.title();
+        out += &format!("\n{}", line(HEADER));
+        for row in &cells {
+            out += &format!("\n{}", line(row.each_ref().map(String::as_str)));
+        }
+        out += "\n";
+        for (name, view) in self.views() {
+            out += &format!("\n{}: {}", name, view);
+        }
+        out
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = HEADER.join(",");
+        for row in self.cells() {
+            let fields: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+            out += &format!("\n{}", fields.join(","));
+        }
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("### {}\n\n", self.title());
+        out += &format!("| {} |\n", HEADER.join(" | "));
+        out += &format!("|{}\n", "---|".repeat(HEADER.len()));
+        for row in self.cells() {
+            let cells: Vec<String> = row.iter().map(|cell| markdown_cell(cell)).collect();
+            out += &format!("| {} |\n", cells.join(" | "));
+        }
+        out += "\n";
+        for (name, view) in self.views() {
+            out += &format!("- {}: `{}`\n", name, view);
+        }
+        out.trim_end().to_string()
+    }
+
+    fn to_json(&self) -> String {
+        let casts: Vec<String> = self
+            .casts
+            .iter()
+            .map(|cast| {
+                let checked = cast.checked.map_or("null".to_string(), |checked| checked.to_string());
+                let lost = match cast.lost {
+                    Loss::None => "null".to_string(),
+                    ref lost => json_string(&lost.to_string()),
+                };
+                format!(
+                    "    {{\"target\": {}, \"as\": {}, \"hex\": {}, \"try_from\": {}, \"lost\": {}}}",
+                    json_string(cast.target),
+                    json_string(&cast.value),
+                    json_string(&cast.hex),
+                    checked,
+                    lost
+                )
+            })
+            .collect();
+        let views: Vec<String> = self
+            .views()
+            .iter()
+            .map(|(name, view)| format!("    {}: {}", json_string(name), json_string(view)))
+            .collect();
+
+        format!(
+            "{{\n  \"type\": {},\n  \"value\": {},\n  \"casts\": [\n{}\n  ],\n  \"views\": {{\n{}\n  }}\n}}",
+            json_string(self.type_name),
+            json_string(&self.value.decimal),
+            casts.join(",\n"),
+            views.join(",\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cast<T: Castable>(value: T, target: &str) -> Cast {
+        value.casts().into_iter().find(|cast| cast.target == target).unwrap()
+    }
+
+    #[test]
+    fn keeps_values_that_fit() {
+        let widened = cast(-5i8, "i64");
+        assert_eq!((widened.value.as_str(), widened.checked, widened.lost), ("-5", Some(true), Loss::None));
+        assert_eq!(cast(200u8, "u8").lost, Loss::None);
+        assert_eq!(cast(7.0f64, "u8").lost, Loss::None);
+        assert_eq!(cast(7.0f64, "u8").checked, None);
+    }
+
+    #[test]
+    fn reinterprets_the_sign() {
+        let unsigned = cast(-1i8, "u8");
+        assert_eq!((unsigned.value.as_str(), unsigned.checked, unsigned.lost), ("255", Some(false), Loss::Sign));
+        assert_eq!(cast(200u8, "i8").lost, Loss::Sign);
+        assert_eq!(cast(200u8, "i8").value, "-56");
+        //E sign extension sets every bit above, yet nothing is lost that `as` back wouldn't restore
+        assert_eq!(cast(-1i64, "u128").lost, Loss::Sign);
+    }
+
+    #[test]
+    fn reports_the_dropped_bits() {
+        let truncated = cast(0x1234u16, "u8");
+        assert_eq!(truncated.value, "52");
+        assert_eq!(truncated.lost, Loss::Truncated { bits: 8..16, dropped: "0x12".to_string() });
+        assert_eq!(truncated.lost.to_string(), "bits 8..16 dropped: 0x12");
+        assert_eq!(cast(-129i16, "i8").lost, Loss::Truncated { bits: 8..16, dropped: "0xff".to_string() });
+    }
+
+    #[test]
+    fn drops_the_fraction_of_floats() {
+        assert_eq!(cast(2.75f64, "i8").value, "2");
+        assert_eq!(cast(2.75f64, "i8").lost, Loss::Fraction);
+        assert_eq!(cast(-0.5f64, "u8").lost, Loss::Fraction);
+        assert_eq!(cast(255.5f32, "u8").lost, Loss::Fraction);
+    }
+
+    #[test]
+    fn saturates_floats_at_the_bounds() {
+        assert_eq!(cast(256.0f64, "u8").lost, Loss::Saturated);
+        assert_eq!(cast(-1.0f64, "u8").lost, Loss::Saturated);
+        assert_eq!(cast(-129.0f64, "i8").lost, Loss::Saturated);
+        assert_eq!(cast(-128.0f64, "i8").lost, Loss::None);
+        assert_eq!(cast(f64::INFINITY, "u128").lost, Loss::Saturated);
+        //E 2^63 saturates to i64::MAX, which converts back to 2^63 in f64: only the bounds tell them apart
+        let top = cast(9223372036854775808.0f64, "i64");
+        assert_eq!((top.value.as_str(), top.lost), ("9223372036854775807", Loss::Saturated));
+        assert_eq!(cast(9223372036854775808.0f64, "u64").lost, Loss::None);
+        assert_eq!(cast(-9223372036854775808.0f64, "i64").lost, Loss::None);
+        assert_eq!(cast(18446744073709551616.0f64, "u64").lost, Loss::Saturated);
+    }
+
+    #[test]
+    fn maps_nan_to_zero() {
+        let nan = cast(f32::NAN, "i32");
+        assert_eq!((nan.value.as_str(), nan.lost), ("0", Loss::NaN));
+        assert_eq!(Loss::NaN.to_string(), "NaN becomes 0");
+    }
+
+    #[test]
+    fn casts_u256_from_the_low_limb() {
+        let small = cast(U256::from(0x1ff), "u8");
+        assert_eq!((small.value.as_str(), small.checked), ("255", Some(false)));
+        assert_eq!(small.lost, Loss::Truncated { bits: 8..256, dropped: "0x1".to_string() });
+        assert_eq!(cast(U256::from(5), "u128").lost, Loss::None);
+
+        //E with a high limb every cast loses the bits from the target width up to 256
+        let wide = U256::MAX.logical_shr(64);
+        let low = cast(wide, "u128");
+        assert_eq!((low.value, low.checked), (u128::MAX.to_string(), Some(false)));
+        assert_eq!(low.lost, Loss::Truncated { bits: 128..256, dropped: format!("{:#x}", u64::MAX) });
+        let dropped = format!("{:#x}", wide.logical_shr(8));
+        assert_eq!(cast(wide, "i8").lost, Loss::Truncated { bits: 8..256, dropped });
+    }
+}
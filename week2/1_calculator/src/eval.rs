@@ -0,0 +1,752 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::arithmetic::{CalcError, ExtendedArithmetic, OverflowPolicy};
+use crate::bits::Bits;
+use crate::calculator::{
+    AdditiveOperations, BinaryOperations, Calculator, MultiplicativeOperations, PowerOperations, UnaryOperations,
+};
+use crate::cast::{build_cast_report, CastReport};
+use crate::expr::{BinaryOp, Expr, ParseError, UnaryOp};
+use crate::registry::{describe, Arity, Registry};
+use crate::report::{build_report, Report};
+use crate::u256::U256;
+
+// Named variables, stored as text so they survive a change of operand type
+pub type Variables = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    Parse(ParseError),
+    UnknownVariable(String),
+    InvalidLiteral { literal: String, type_name: &'static str },
+    Unsupported { operation: &'static str, type_name: &'static str },
+    UnknownFunction(String),
+    Arity { function: &'static str, expected: Arity, found: usize },
+    Calc(CalcError),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Parse(err) => write!(f, "{}", err),
+            EvalError::UnknownVariable(name) => write!(f, "unknown variable `{}`", name),
+            EvalError::InvalidLiteral { literal, type_name } => {
+                write!(f, "`{}` is not a valid {}", literal, type_name)
+            }
+            EvalError::Unsupported { operation, type_name } => {
+                write!(f, "`{}` is not supported for {}", operation, type_name)
+            }
+            EvalError::UnknownFunction(name) => write!(f, "unknown function `{}`", name),
+            EvalError::Arity { function, expected, found } => {
+                write!(f, "`{}` takes {} argument(s), {} given", function, expected, found)
+            }
+            EvalError::Calc(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl From<ParseError> for EvalError {
+    fn from(err: ParseError) -> Self {
+        EvalError::Parse(err)
+    }
+}
+
+impl From<CalcError> for EvalError {
+    fn from(err: CalcError) -> Self {
+        EvalError::Calc(err)
+    }
+}
+
+/** OPERAND
+ * A type the expression evaluator can run on
+ * Every binary operator is dispatched to the Calculator traits, so expressions follow
+ * exactly the same overflow rules as the operations printed by Display
+ */
+pub trait Operand: ExtendedArithmetic + fmt::Display + 'static {
+    const NAME: &'static str;
+    // true when the type has negative values, floats included
+    const SIGNED: bool;
+
+    fn zero() -> Self;
+
+    // Decimal literals, plus 0x / 0o / 0b prefixes for integers; `_` separators are ignored
+    fn parse_literal(literal: &str) -> Option<Self>;
+
+    // Two's complement bits for integers, IEEE-754 bits for floats
+    fn to_hex(self) -> String;
+    fn to_binary(self) -> String;
+
+    // The bit-level operations below return None when the type has none (floats)
+    fn bitwise(op: BinaryOp, calc: &Calculator<Self>) -> Option<Result<Self, CalcError>>;
+    fn not(self) -> Option<Self>;
+    fn function(function: Function, args: &[Self]) -> Option<Result<Self, CalcError>>;
+
+    // a * b / d without intermediate overflow under the policy, for types with a full-width product (U256)
+    fn full_mul_div(_a: Self, _b: Self, _d: Self, _policy: OverflowPolicy) -> Option<Result<(Self, bool), CalcError>> {
+        None
+    }
+}
+
+// Functions callable from expressions, e.g. `extract(flags, 4, 2)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    RotateLeft,
+    RotateRight,
+    PopCount,
+    LeadingZeros,
+    TrailingZeros,
+    Extract,
+    Insert,
+    Low,
+    High,
+    MulHigh,
+    MulDiv,
+}
+
+impl Function {
+    pub const ALL: [Function; 11] = [
+        Function::RotateLeft,
+        Function::RotateRight,
+        Function::PopCount,
+        Function::LeadingZeros,
+        Function::TrailingZeros,
+        Function::Extract,
+        Function::Insert,
+        Function::Low,
+        Function::High,
+        Function::MulHigh,
+        Function::MulDiv,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::RotateLeft => "rotl",
+            Function::RotateRight => "rotr",
+            Function::PopCount => "popcount",
+            Function::LeadingZeros => "clz",
+            Function::TrailingZeros => "ctz",
+            Function::Extract => "extract",
+            Function::Insert => "insert",
+            Function::Low => "low",
+            Function::High => "high",
+            Function::MulHigh => "mulhi",
+            Function::MulDiv => "muldiv",
+        }
+    }
+
+    pub fn signature(self) -> &'static str {
+        match self {
+            Function::RotateLeft => "rotl(x, n)",
+            Function::RotateRight => "rotr(x, n)",
+            Function::PopCount => "popcount(x)",
+            Function::LeadingZeros => "clz(x)",
+            Function::TrailingZeros => "ctz(x)",
+            Function::Extract => "extract(x, start, len)",
+            Function::Insert => "insert(x, field, start, len)",
+            Function::Low => "low(x)",
+            Function::High => "high(x)",
+            Function::MulHigh => "mulhi(a, b)",
+            Function::MulDiv => "muldiv(a, b, d)",
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Function::PopCount
+            | Function::LeadingZeros
+            | Function::TrailingZeros
+            | Function::Low
+            | Function::High => 1,
+            Function::RotateLeft | Function::RotateRight | Function::MulHigh => 2,
+            Function::Extract | Function::MulDiv => 3,
+            Function::Insert => 4,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Function> {
+        Function::ALL.into_iter().find(|function| function.name() == name)
+    }
+}
+
+fn bitwise<T: Bits>(op: BinaryOp, calc: &Calculator<T>) -> Option<Result<T, CalcError>> {
+    let result = match op {
+        BinaryOp::And => Ok(calc.bitwise_and()),
+        BinaryOp::Or => Ok(calc.bitwise_or()),
+        BinaryOp::Xor => Ok(calc.bitwise_xor()),
+        BinaryOp::Shl => calc.shift_left(),
+        //E `>>` keeps Rust semantics: arithmetic on signed types, logical on unsigned ones
+        BinaryOp::Shr if T::SIGNED => calc.shift_right_arithmetic(),
+        BinaryOp::Shr | BinaryOp::LogicalShr => calc.shift_right_logical(),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn bit_function<T: Bits>(function: Function, args: &[T]) -> Result<T, CalcError> {
+    let calc = |m1: T, m2: T| Calculator { m1, m2 };
+    //E a negative or huge bit index is out of range whatever the type
+    let index = |value: T| value.to_bit_index().unwrap_or(u32::MAX);
+
+    match (function, args) {
+        (Function::RotateLeft, &[x, n]) => calc(x, n).rotate_left(),
+        (Function::RotateRight, &[x, n]) => calc(x, n).rotate_right(),
+        (Function::PopCount, &[x]) => Ok(T::from_count(calc(x, T::ZERO).count_ones())),
+        (Function::LeadingZeros, &[x]) => Ok(T::from_count(calc(x, T::ZERO).leading_zeros())),
+        (Function::TrailingZeros, &[x]) => Ok(T::from_count(calc(x, T::ZERO).trailing_zeros())),
+        (Function::Extract, &[x, start, len]) => calc(x, T::ZERO).extract_bits(index(start), index(len)),
+        (Function::Insert, &[x, field, start, len]) => calc(x, field).insert_bits(index(start), index(len)),
+        //E low / high halves: the 128-bit limbs of a U256, the 64-bit halves of a u128, ...
+        (Function::Low, &[x]) => calc(x, T::ZERO).extract_bits(0, T::BITS / 2),
+        (Function::High, &[x]) => calc(x, T::ZERO).extract_bits(T::BITS / 2, T::BITS / 2),
+        _ => unreachable!("arity is checked, mulhi and muldiv are handled by the caller"),
+    }
+}
+
+macro_rules! impl_integer_operand {
+    ($($t:ty),*) => {
+        $(
+            impl Operand for $t {
+                const NAME: &'static str = stringify!($t);
+                const SIGNED: bool = <$t>::MIN != 0;
+
+                fn zero() -> Self {
+                    0
+                }
+
+                fn parse_literal(literal: &str) -> Option<Self> {
+                    let literal = literal.replace('_', "");
+                    let (sign, digits) = match literal.strip_prefix('-') {
+                        Some(rest) => ("-", rest),
+                        None => ("", literal.as_str()),
+                    };
+                    let (radix, digits) = split_radix(digits);
+                    <$t>::from_str_radix(&format!("{}{}", sign, digits), radix).ok()
+                }
+
+                fn to_hex(self) -> String {
+                    format!("{:#x}", self)
+                }
+
+                fn to_binary(self) -> String {
+                    format!("{:#b}", self)
+                }
+
+                fn bitwise(op: BinaryOp, calc: &Calculator<Self>) -> Option<Result<Self, CalcError>> {
+                    bitwise(op, calc)
+                }
+
+                fn not(self) -> Option<Self> {
+                    Some(Calculator { m1: self, m2: 0 }.bitwise_not())
+                }
+
+                fn function(function: Function, args: &[Self]) -> Option<Result<Self, CalcError>> {
+                    match (function, args) {
+                        //E high half of the exact product, computed in 256 bits; meaningless for signed types
+                        (Function::MulHigh, &[a, b]) if <$t>::MIN == 0 => {
+                            let (product, _) = U256::from(a as u128).full_mul(U256::from(b as u128));
+                            Some(Ok(product.logical_shr(<$t>::BITS).low() as $t))
+                        }
+                        (Function::MulHigh, _) => None,
+                        _ => Some(bit_function(function, args)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_float_operand {
+    ($($t:ty),*) => {
+        $(
+            impl Operand for $t {
+                const NAME: &'static str = stringify!($t);
+                const SIGNED: bool = true;
+
+                fn zero() -> Self {
+                    0.0
+                }
+
+                fn parse_literal(literal: &str) -> Option<Self> {
+                    literal.replace('_', "").parse().ok()
+                }
+
+                //E every bit of the encoding is shown, so sign, exponent and mantissa line up
+                fn to_hex(self) -> String {
+                    format!("{:#0width$x}", self.to_bits(), width = 2 + std::mem::size_of::<$t>() * 2)
+                }
+
+                fn to_binary(self) -> String {
+                    format!("{:#0width$b}", self.to_bits(), width = 2 + std::mem::size_of::<$t>() * 8)
+                }
+
+                fn bitwise(_op: BinaryOp, _calc: &Calculator<Self>) -> Option<Result<Self, CalcError>> {
+                    None
+                }
+
+                fn not(self) -> Option<Self> {
+                    None
+                }
+
+                fn function(_function: Function, _args: &[Self]) -> Option<Result<Self, CalcError>> {
+                    None
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_operand!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+impl_float_operand!(f32, f64);
+
+impl Operand for U256 {
+    const NAME: &'static str = "u256";
+    const SIGNED: bool = false;
+
+    fn zero() -> Self {
+        U256::ZERO
+    }
+
+    fn parse_literal(literal: &str) -> Option<Self> {
+        let literal = literal.replace('_', "");
+        let (radix, digits) = split_radix(&literal);
+        U256::from_str_radix(digits, radix).ok()
+    }
+
+    fn to_hex(self) -> String {
+        format!("{:#x}", self)
+    }
+
+    fn to_binary(self) -> String {
+        format!("{:#b}", self)
+    }
+
+    fn bitwise(op: BinaryOp, calc: &Calculator<Self>) -> Option<Result<Self, CalcError>> {
+        bitwise(op, calc)
+    }
+
+    fn not(self) -> Option<Self> {
+        Some(Calculator { m1: self, m2: U256::ZERO }.bitwise_not())
+    }
+
+    fn function(function: Function, args: &[Self]) -> Option<Result<Self, CalcError>> {
+        match (function, args) {
+            (Function::MulHigh, &[a, b]) => Some(Ok(a.full_mul(b).1)),
+            (Function::Low, &[x]) => Some(Ok(U256::from(x.low()))),
+            (Function::High, &[x]) => Some(Ok(U256::from(x.high()))),
+            _ => Some(bit_function(function, args)),
+        }
+    }
+
+    fn full_mul_div(a: Self, b: Self, d: Self, policy: OverflowPolicy) -> Option<Result<(Self, bool), CalcError>> {
+        //E only the quotient can overflow, the product always fits in 512 bits
+        Some(match policy {
+            OverflowPolicy::Checked => a.mul_div(b, d).map(|quotient| (quotient, false)),
+            OverflowPolicy::Saturating => a
+                .overflowing_mul_div(b, d)
+                .map(|(quotient, overflowed)| (if overflowed { U256::MAX } else { quotient }, overflowed)),
+            OverflowPolicy::Wrapping | OverflowPolicy::Overflowing => a.overflowing_mul_div(b, d),
+        })
+    }
+}
+
+fn split_radix(digits: &str) -> (u32, &str) {
+    let lower = digits.get(..2).map(|prefix| prefix.to_ascii_lowercase());
+    match lower.as_deref() {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, digits),
+    }
+}
+
+// One evaluated operator with its operands substituted, e.g. `200 + 100 = 44`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub expression: String,
+    pub value: String,
+    pub overflowed: bool,
+    // extra detail worth flagging, e.g. the precision lost by a rounded fixed-point division
+    pub note: Option<String>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.expression, self.value)?;
+        if self.overflowed {
+            write!(f, " (overflow)")?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, " ({})", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub value: String,
+    pub overflowed: bool,
+    pub steps: Vec<Step>,
+}
+
+struct Evaluator<'a> {
+    vars: &'a Variables,
+    policy: OverflowPolicy,
+    steps: Vec<Step>,
+}
+
+impl Evaluator<'_> {
+    fn literal<T: Operand>(&self, literal: &str) -> Result<T, EvalError> {
+        T::parse_literal(literal).ok_or_else(|| EvalError::InvalidLiteral {
+            literal: literal.to_string(),
+            type_name: T::NAME,
+        })
+    }
+
+    fn eval<T: Operand>(&mut self, expr: &Expr) -> Result<T, EvalError> {
+        match expr {
+            Expr::Number(literal) => self.literal(literal),
+            Expr::Variable(name) => {
+                let value = self
+                    .vars
+                    .get(name)
+                    .ok_or_else(|| EvalError::UnknownVariable(name.clone()))?;
+                self.literal(value)
+            }
+            Expr::Unary(UnaryOp::Neg, operand) => {
+                //E on signed types a negative literal is parsed as is, so `-128` is a valid i8 even though 128 is not
+                //E unsigned types negate under the policy instead, so `-1` is 255 on a wrapping u8 like `0 - 1`
+                if let (true, Expr::Number(literal)) = (T::SIGNED, operand.as_ref()) {
+                    if let Some(value) = T::parse_literal(&format!("-{}", literal)) {
+                        return Ok(value);
+                    }
+                }
+                let value = self.eval::<T>(operand)?;
+                let calc = Calculator { m1: value, m2: T::zero() };
+                let (result, overflowed) = calc.negate_with(self.policy)?;
+                self.record(format!("-{}", value), result, overflowed);
+                Ok(result)
+            }
+            Expr::Unary(UnaryOp::Not, operand) => {
+                let value = self.eval::<T>(operand)?;
+                let result = value.not().ok_or(EvalError::Unsupported {
+                    operation: UnaryOp::Not.symbol(),
+                    type_name: T::NAME,
+                })?;
+                self.record(format!("!{}", value), result, false);
+                Ok(result)
+            }
+            Expr::Call(name, args) => {
+                let Some(function) = Function::from_name(name) else {
+                    return self.call_registered::<T>(name, args);
+                };
+                if args.len() != function.arity() {
+                    return Err(EvalError::Arity {
+                        function: function.name(),
+                        expected: Arity::Exactly(function.arity()),
+                        found: args.len(),
+                    });
+                }
+                let values = args.iter().map(|arg| self.eval::<T>(arg)).collect::<Result<Vec<T>, _>>()?;
+                let shown: Vec<String> = values.iter().map(T::to_string).collect();
+
+                //E muldiv is a multiplication then a division, both under the selected policy
+                if function == Function::MulDiv {
+                    if let Some(result) = T::full_mul_div(values[0], values[1], values[2], self.policy) {
+                        let (result, overflowed) = result?;
+                        self.record(format!("{}({})", name, shown.join(", ")), result, overflowed);
+                        return Ok(result);
+                    }
+                    let (product, product_overflowed) = self.apply(BinaryOp::Mul, values[0], values[1])?;
+                    let (result, overflowed) = self.apply(BinaryOp::Div, product, values[2])?;
+                    self.record(format!("{}({})", name, shown.join(", ")), result, product_overflowed || overflowed);
+                    return Ok(result);
+                }
+
+                let result = T::function(function, &values).ok_or(EvalError::Unsupported {
+                    operation: function.name(),
+                    type_name: T::NAME,
+                })??;
+                self.record(format!("{}({})", name, shown.join(", ")), result, false);
+                Ok(result)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval::<T>(lhs)?;
+                let rhs = self.eval::<T>(rhs)?;
+                let (result, overflowed) = self.apply(*op, lhs, rhs)?;
+                self.record(format!("{} {} {}", lhs, op.symbol(), rhs), result, overflowed);
+                Ok(result)
+            }
+        }
+    }
+
+    // Any registered operation is callable by name, e.g. `mean(a, b, c)`
+    fn call_registered<T: Operand>(&mut self, name: &str, args: &[Expr]) -> Result<T, EvalError> {
+        let registry = Registry::<T>::standard();
+        let operation = registry.get(name).ok_or_else(|| EvalError::UnknownFunction(name.to_string()))?;
+        if !operation.arity().accepts(args.len()) {
+            return Err(EvalError::Arity {
+                function: operation.name(),
+                expected: operation.arity(),
+                found: args.len(),
+            });
+        }
+        let values = args.iter().map(|arg| self.eval::<T>(arg)).collect::<Result<Vec<T>, _>>()?;
+        let (result, overflowed) = operation.evaluate(&values, self.policy).ok_or(EvalError::Unsupported {
+            operation: operation.name(),
+            type_name: T::NAME,
+        })??;
+        let shown: Vec<String> = values.iter().map(T::to_string).collect();
+        self.record(format!("{}({})", name, shown.join(", ")), result, overflowed);
+        Ok(result)
+    }
+
+    fn apply<T: Operand>(&self, op: BinaryOp, lhs: T, rhs: T) -> Result<(T, bool), EvalError> {
+        let calc = Calculator { m1: lhs, m2: rhs };
+        let result = match op {
+            BinaryOp::Add => calc.add_with(self.policy)?,
+            BinaryOp::Sub => calc.subtract_with(self.policy)?,
+            BinaryOp::Mul => calc.multiply_with(self.policy)?,
+            BinaryOp::Div => calc.divide_with(self.policy)?,
+            BinaryOp::Pow => calc.power_with(self.policy)?,
+            _ => {
+                let value = T::bitwise(op, &calc).ok_or(EvalError::Unsupported {
+                    operation: op.symbol(),
+                    type_name: T::NAME,
+                })??;
+                (value, false)
+            }
+        };
+        Ok(result)
+    }
+
+    fn record<T: Operand>(&mut self, expression: String, value: T, overflowed: bool) {
+        self.steps.push(Step {
+            expression,
+            value: value.to_string(),
+            overflowed,
+            note: None,
+        });
+    }
+}
+
+pub fn evaluate<T: Operand>(expr: &Expr, vars: &Variables, policy: OverflowPolicy) -> Result<Evaluation, EvalError> {
+    let mut evaluator = Evaluator {
+        vars,
+        policy,
+        steps: Vec::new(),
+    };
+    let value = evaluator.eval::<T>(expr)?;
+    Ok(Evaluation {
+        value: value.to_string(),
+        overflowed: evaluator.steps.iter().any(|step| step.overflowed),
+        steps: evaluator.steps,
+    })
+}
+
+// Operand types selectable at runtime (REPL `:type`, CLI `--type`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumType {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    U256,
+    F32,
+    F64,
+}
+
+// Calls a generic function with the Rust type matching a NumType
+macro_rules! dispatch {
+    ($num_type:expr, $f:ident($($arg:expr),*)) => {
+        match $num_type {
+            $crate::eval::NumType::I8 => $f::<i8>($($arg),*),
+            $crate::eval::NumType::I16 => $f::<i16>($($arg),*),
+            $crate::eval::NumType::I32 => $f::<i32>($($arg),*),
+            $crate::eval::NumType::I64 => $f::<i64>($($arg),*),
+            $crate::eval::NumType::I128 => $f::<i128>($($arg),*),
+            $crate::eval::NumType::U8 => $f::<u8>($($arg),*),
+            $crate::eval::NumType::U16 => $f::<u16>($($arg),*),
+            $crate::eval::NumType::U32 => $f::<u32>($($arg),*),
+            $crate::eval::NumType::U64 => $f::<u64>($($arg),*),
+            $crate::eval::NumType::U128 => $f::<u128>($($arg),*),
+            $crate::eval::NumType::U256 => $f::<$crate::u256::U256>($($arg),*),
+            $crate::eval::NumType::F32 => $f::<f32>($($arg),*),
+            $crate::eval::NumType::F64 => $f::<f64>($($arg),*),
+        }
+    };
+}
+pub(crate) use dispatch;
+
+impl NumType {
+    pub const ALL: [NumType; 13] = [
+        NumType::I8,
+        NumType::I16,
+        NumType::I32,
+        NumType::I64,
+        NumType::I128,
+        NumType::U8,
+        NumType::U16,
+        NumType::U32,
+        NumType::U64,
+        NumType::U128,
+        NumType::U256,
+        NumType::F32,
+        NumType::F64,
+    ];
+
+    pub fn name(self) -> &'static str {
+        fn name_of<T: Operand>() -> &'static str {
+            T::NAME
+        }
+        dispatch!(self, name_of())
+    }
+
+    pub fn evaluate(self, expr: &Expr, vars: &Variables, policy: OverflowPolicy) -> Result<Evaluation, EvalError> {
+        dispatch!(self, evaluate(expr, vars, policy))
+    }
+
+    pub fn report(self, m1: &str, m2: &str) -> Result<Report, EvalError> {
+        dispatch!(self, build_report(m1, m2))
+    }
+
+    // `literal` of this type cast to every integer type
+    pub fn casts(self, literal: &str) -> Result<CastReport, EvalError> {
+        dispatch!(self, build_cast_report(literal))
+    }
+
+    // name, symbol, arity and label of the operations registered for this type
+    pub fn operations(self) -> Vec<[String; 4]> {
+        dispatch!(self, describe())
+    }
+}
+
+impl fmt::Display for NumType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NumType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NumType::ALL
+            .into_iter()
+            .find(|num_type| num_type.name() == s)
+            .ok_or_else(|| format!("unknown type `{}`", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{parse_statement, Statement};
+
+    fn run(num_type: NumType, policy: OverflowPolicy, input: &str, vars: &Variables) -> Result<Evaluation, EvalError> {
+        let expr = match parse_statement(input)? {
+            Statement::Expr(expr) | Statement::Assign(_, expr) => expr,
+        };
+        num_type.evaluate(&expr, vars, policy)
+    }
+
+    fn value(num_type: NumType, policy: OverflowPolicy, input: &str) -> Result<String, EvalError> {
+        run(num_type, policy, input, &Variables::new()).map(|evaluation| evaluation.value)
+    }
+
+    fn checked(num_type: NumType, input: &str) -> String {
+        value(num_type, OverflowPolicy::Checked, input).unwrap()
+    }
+
+    #[test]
+    fn evaluates_by_precedence() {
+        assert_eq!(checked(NumType::I64, "1 + 2 * 3"), "7");
+        assert_eq!(checked(NumType::I64, "(1 + 2) * 3"), "9");
+        assert_eq!(checked(NumType::I64, "2 ** 3 ** 2"), "512");
+        assert_eq!(checked(NumType::I64, "-2 ** 2"), "4");
+        assert_eq!(checked(NumType::I64, "1 | 6 & 3"), "3");
+        assert_eq!(checked(NumType::I64, "100 - 10 - 1"), "89");
+    }
+
+    #[test]
+    fn shifts_logically_with_three_arrows() {
+        assert_eq!(checked(NumType::I8, "-128 >> 1"), "-64");
+        assert_eq!(checked(NumType::I8, "-128 >>> 1"), "64");
+        assert_eq!(checked(NumType::U8, "128 >> 1"), "64");
+        assert_eq!(checked(NumType::U8, "128 >>> 1"), "64");
+    }
+
+    #[test]
+    fn reads_variables_and_the_last_result() {
+        let vars = Variables::from([("x".to_string(), "40".to_string()), ("_".to_string(), "2".to_string())]);
+        let evaluation = run(NumType::I32, OverflowPolicy::Checked, "y = x + _", &vars).unwrap();
+        assert_eq!(evaluation.value, "42");
+        assert_eq!(evaluation.steps[0].expression, "40 + 2");
+
+        let err = run(NumType::I32, OverflowPolicy::Checked, "x + z", &vars).unwrap_err();
+        assert_eq!(err, EvalError::UnknownVariable("z".to_string()));
+        assert_eq!(err.to_string(), "unknown variable `z`");
+    }
+
+    #[test]
+    fn reports_parse_errors_with_their_position() {
+        let err = value(NumType::I64, OverflowPolicy::Checked, "1 + (2").unwrap_err();
+        assert_eq!(err.to_string(), "parse error at column 7: expected `)`");
+    }
+
+    #[test]
+    fn parses_negative_literals_on_signed_types() {
+        assert_eq!(checked(NumType::I8, "-128"), "-128");
+        assert_eq!(checked(NumType::I128, "-0x80"), "-128");
+        assert_eq!(checked(NumType::F64, "-2.5"), "-2.5");
+        assert!(matches!(value(NumType::I8, OverflowPolicy::Checked, "-(127 + 1)"), Err(EvalError::Calc(_))));
+        assert!(matches!(value(NumType::I8, OverflowPolicy::Checked, "-129"), Err(EvalError::InvalidLiteral { .. })));
+    }
+
+    #[test]
+    fn negates_unsigned_literals_under_the_policy() {
+        let wrapping = |input| value(NumType::U8, OverflowPolicy::Wrapping, input).unwrap();
+        assert_eq!(wrapping("-1"), "255");
+        assert_eq!(wrapping("-1"), wrapping("0 - 1"));
+        assert_eq!(value(NumType::U8, OverflowPolicy::Saturating, "-1").unwrap(), "0");
+        assert_eq!(value(NumType::U256, OverflowPolicy::Wrapping, "-1").unwrap(), U256::MAX.to_string());
+        assert_eq!(checked(NumType::U8, "-0"), "0");
+        let checked = value(NumType::U8, OverflowPolicy::Checked, "-1");
+        assert!(matches!(checked, Err(EvalError::Calc(CalcError::Overflow { .. }))));
+
+        let evaluation = run(NumType::U16, OverflowPolicy::Overflowing, "-1", &Variables::new()).unwrap();
+        assert_eq!(evaluation.value, "65535");
+        assert!(evaluation.overflowed);
+    }
+
+    #[test]
+    fn mul_divs_u256_under_the_policy() {
+        //E 2^255 * 6 / 2 is 3 * 2^255, one bit past the range
+        let input = format!("muldiv({}, 6, 2)", U256::ONE.shl(255));
+        let wrapped = run(NumType::U256, OverflowPolicy::Wrapping, &input, &Variables::new()).unwrap();
+        assert_eq!(wrapped.value, U256::ONE.shl(255).to_string());
+        assert!(wrapped.overflowed);
+        let overflowing = run(NumType::U256, OverflowPolicy::Overflowing, &input, &Variables::new()).unwrap();
+        assert_eq!(overflowing.value, wrapped.value);
+        assert!(overflowing.overflowed);
+        let saturated = run(NumType::U256, OverflowPolicy::Saturating, &input, &Variables::new()).unwrap();
+        assert_eq!(saturated.value, U256::MAX.to_string());
+        assert!(saturated.overflowed);
+        let checked = value(NumType::U256, OverflowPolicy::Checked, &input);
+        assert!(matches!(checked, Err(EvalError::Calc(CalcError::Overflow { .. }))));
+
+        let in_range = run(NumType::U256, OverflowPolicy::Wrapping, "muldiv(7, 3, 2)", &Variables::new()).unwrap();
+        assert_eq!(in_range.value, "10");
+        assert!(!in_range.overflowed);
+    }
+}
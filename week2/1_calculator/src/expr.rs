@@ -0,0 +1,467 @@
+use std::fmt;
+
+/** EXPRESSIONS
+ * Infix expressions over literals and named variables, e.g. `(a + b) * c / d ^ e & f`
+ * Operator precedence follows Rust, from loosest to tightest binding:
+ *  - `|`
+ *  - `^`
+ *  - `&`
+ *  - `<<` `>>` `>>>`
+ *  - `+` `-`
+ *  - `*` `/`
+ *  - `**`
+ *  - unary `-` `!`
+ *
+ * `>>` shifts like Rust does (arithmetic for signed types, logical for unsigned ones),
+ * `>>>` is always a logical shift. Functions are called as `name(arg, ...)`
+ *
+ * `**` is right associative (`2 ** 3 ** 2` is `2 ** 9`) and, unlike in Python, binds looser
+ * than unary minus: `-2 ** 2` is `(-2) ** 2`. The other binary operators are left associative,
+ * parentheses override precedence
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    LogicalShr,
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Pow => "**",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::LogicalShr => ">>>",
+        }
+    }
+
+    // Higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::Xor => 2,
+            BinaryOp::And => 3,
+            BinaryOp::Shl | BinaryOp::Shr | BinaryOp::LogicalShr => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div => 6,
+            BinaryOp::Pow => 7,
+        }
+    }
+
+    fn right_associative(self) -> bool {
+        self == BinaryOp::Pow
+    }
+
+    fn from_token(token: &Token) -> Option<BinaryOp> {
+        match token {
+            Token::Plus => Some(BinaryOp::Add),
+            Token::Minus => Some(BinaryOp::Sub),
+            Token::Star => Some(BinaryOp::Mul),
+            Token::Slash => Some(BinaryOp::Div),
+            Token::Pow => Some(BinaryOp::Pow),
+            Token::Ampersand => Some(BinaryOp::And),
+            Token::Pipe => Some(BinaryOp::Or),
+            Token::Caret => Some(BinaryOp::Xor),
+            Token::Shl => Some(BinaryOp::Shl),
+            Token::Shr => Some(BinaryOp::Shr),
+            Token::LogicalShr => Some(BinaryOp::LogicalShr),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl UnaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    //E literals are kept as text, the evaluator parses them into the selected type
+    Number(String),
+    Variable(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(literal) => write!(f, "{}", literal),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::Unary(op, operand) => write!(f, "{}{}", op.symbol(), operand),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op.symbol(), rhs),
+            Expr::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+        }
+    }
+}
+
+// A line of input: either `name = expr` or a bare expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Assign(String, Expr),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "parse error at column {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Pow,
+    Slash,
+    Ampersand,
+    Pipe,
+    Caret,
+    Shl,
+    Shr,
+    LogicalShr,
+    Bang,
+    Comma,
+    LParen,
+    RParen,
+    Equals,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Token::Number(literal) => literal,
+            Token::Ident(name) => name,
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Pow => "**",
+            Token::Slash => "/",
+            Token::Ampersand => "&",
+            Token::Pipe => "|",
+            Token::Caret => "^",
+            Token::Shl => "<<",
+            Token::Shr => ">>",
+            Token::LogicalShr => ">>>",
+            Token::Bang => "!",
+            Token::Comma => ",",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::Equals => "=",
+        };
+        f.write_str(text)
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        //E numbers take every alphanumeric char so `0xff`, `0b1010`, `1_000` and `2.5` stay one token
+        if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push((Token::Number(chars[start..i].iter().collect()), start));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), start));
+            continue;
+        }
+
+        //E multi-char operators, longest first
+        let rest: String = chars[i..].iter().take(3).collect();
+        let multi = [(">>>", Token::LogicalShr), ("<<", Token::Shl), (">>", Token::Shr), ("**", Token::Pow)]
+            .into_iter()
+            .find(|(symbol, _)| rest.starts_with(symbol));
+        if let Some((symbol, token)) = multi {
+            tokens.push((token, start));
+            i += symbol.len();
+            continue;
+        }
+
+        let token = match c {
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '&' => Token::Ampersand,
+            '|' => Token::Pipe,
+            '^' => Token::Caret,
+            '!' => Token::Bang,
+            ',' => Token::Comma,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' => Token::Equals,
+            other => {
+                return Err(ParseError {
+                    message: format!("unexpected character `{}`", other),
+                    position: start,
+                })
+            }
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(_, position)| *position).unwrap_or(self.end)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            position: self.position(),
+        }
+    }
+
+    // Precedence climbing: parse operators binding at least as tight as `min_precedence`
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.peek().and_then(BinaryOp::from_token) {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.next();
+            //E a right associative operator takes the rest of the chain as its right operand
+            let next_precedence = if op.right_associative() { op.precedence() } else { op.precedence() + 1 };
+            let rhs = self.expression(next_precedence)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let op = match self.peek() {
+            Some(Token::Minus) => UnaryOp::Neg,
+            Some(Token::Bang) => UnaryOp::Not,
+            _ => return self.primary(),
+        };
+        self.next();
+        let operand = self.unary()?;
+        Ok(Expr::Unary(op, Box::new(operand)))
+    }
+
+    // Arguments of `name(...)`, the opening parenthesis is already consumed
+    fn arguments(&mut self) -> Result<Vec<Expr>, ParseError> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.next();
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression(0)?);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => return Ok(args),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected `,` or `)`"));
+                }
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Number(literal)) => Ok(Expr::Number(literal)),
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::LParen) => {
+                self.next();
+                Ok(Expr::Call(name, self.arguments()?))
+            }
+            Some(Token::Ident(name)) => Ok(Expr::Variable(name)),
+            Some(Token::LParen) => {
+                let inner = self.expression(0)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err(ParseError {
+                        message: "expected `)`".to_string(),
+                        position: self.tokens.get(self.pos - 1).map(|(_, p)| *p).unwrap_or(self.end),
+                    }),
+                }
+            }
+            Some(token) => Err(ParseError {
+                message: format!("unexpected `{}`", token),
+                position,
+            }),
+            None => Err(ParseError {
+                message: "unexpected end of input".to_string(),
+                position,
+            }),
+        }
+    }
+}
+
+fn parse_tokens(tokens: Vec<(Token, usize)>, end: usize) -> Result<Expr, ParseError> {
+    let mut parser = Parser { tokens, pos: 0, end };
+    let expr = parser.expression(0)?;
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected trailing input"));
+    }
+    Ok(expr)
+}
+
+pub fn parse_statement(input: &str) -> Result<Statement, ParseError> {
+    let mut tokens = tokenize(input)?;
+    let end = input.chars().count();
+
+    //E `name = expr` is an assignment, anything else is evaluated as an expression
+    if let [(Token::Ident(name), _), (Token::Equals, _), ..] = tokens.as_slice() {
+        let name = name.clone();
+        let expr = parse_tokens(tokens.split_off(2), end)?;
+        return Ok(Statement::Assign(name, expr));
+    }
+
+    parse_tokens(tokens, end).map(Statement::Expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Expr {
+        match parse_statement(input) {
+            Ok(Statement::Expr(expr)) => expr,
+            other => panic!("expected an expression for `{}`, got {:?}", input, other),
+        }
+    }
+
+    fn error(input: &str) -> ParseError {
+        parse_statement(input).expect_err(input)
+    }
+
+    #[test]
+    fn binds_by_precedence() {
+        assert_eq!(parse("1 + 2 * 3").to_string(), "(1 + (2 * 3))");
+        assert_eq!(parse("1 | 2 ^ 3 & 4").to_string(), "(1 | (2 ^ (3 & 4)))");
+        assert_eq!(parse("1 << 2 + 3").to_string(), "(1 << (2 + 3))");
+        assert_eq!(parse("2 * 3 ** 2").to_string(), "(2 * (3 ** 2))");
+        assert_eq!(parse("(1 + 2) * 3").to_string(), "((1 + 2) * 3)");
+    }
+
+    #[test]
+    fn associates_left_except_pow() {
+        assert_eq!(parse("1 - 2 - 3").to_string(), "((1 - 2) - 3)");
+        assert_eq!(parse("8 / 4 / 2").to_string(), "((8 / 4) / 2)");
+        assert_eq!(parse("2 ** 3 ** 2").to_string(), "(2 ** (3 ** 2))");
+    }
+
+    #[test]
+    fn unary_minus_binds_tighter_than_pow() {
+        let minus_two = Expr::Unary(UnaryOp::Neg, Box::new(Expr::Number("2".into())));
+        let expected = Expr::Binary(BinaryOp::Pow, Box::new(minus_two), Box::new(Expr::Number("2".into())));
+        assert_eq!(parse("-2 ** 2"), expected);
+    }
+
+    #[test]
+    fn tells_logical_from_arithmetic_shift() {
+        let shift = |op| Expr::Binary(op, Box::new(Expr::Variable("a".into())), Box::new(Expr::Number("1".into())));
+        assert_eq!(parse("a >>> 1"), shift(BinaryOp::LogicalShr));
+        assert_eq!(parse("a >> 1"), shift(BinaryOp::Shr));
+        //E `>>>` is one token, not `>>` followed by `>`
+        assert_eq!(parse("a >>> 1 >> 2").to_string(), "((a >>> 1) >> 2)");
+    }
+
+    #[test]
+    fn parses_calls_and_literals() {
+        assert_eq!(parse("rotl(0xff, 1_000)").to_string(), "rotl(0xff, 1_000)");
+        assert_eq!(parse("f()"), Expr::Call("f".into(), Vec::new()));
+    }
+
+    #[test]
+    fn parses_assignments() {
+        assert_eq!(parse_statement("x = _ + 1"), Ok(Statement::Assign("x".into(), parse("_ + 1"))));
+        assert_eq!(parse("_"), Expr::Variable("_".into()));
+        //E only a leading `name =` assigns
+        assert!(parse_statement("1 = 2").is_err());
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error("1 + $"), ParseError { message: "unexpected character `$`".into(), position: 4 });
+        assert_eq!(error("(1 + 2"), ParseError { message: "expected `)`".into(), position: 6 });
+        assert_eq!(error("1 + 2)"), ParseError { message: "unexpected trailing input".into(), position: 5 });
+        assert_eq!(error("1 +").message, "unexpected end of input");
+        assert_eq!(error("1 + $").to_string(), "parse error at column 5: unexpected character `$`");
+    }
+}
@@ -0,0 +1,68 @@
+use crate::eval::Operand;
+use crate::registry::Registry;
+
+/** EXTENSIONS
+ * Domain-specific operations, registered after the built-in ones in `Registry::standard`
+ * Implement `Operation<T>` for any `T: Operand` and register it below: it then shows up in
+ * the Calculator Display, `--report`, `--ops` in batch mode, `--list-ops` and as an expression function
+ *
+ *     registry.register(Box::new(Square)).expect("extension names don't clash with built-ins");
+ *
+ * None are registered yet; `Square` in the tests is a complete example
+ */
+pub fn register<T: Operand>(_registry: &mut Registry<T>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arithmetic::{CalcError, OverflowPolicy};
+    use crate::calculator::{Calculator, MultiplicativeOperations};
+    use crate::registry::{apply, Arity, Operation};
+
+    // m1 * m1 under the selected policy, e.g. the `amount * amount` in quadratic reward curves
+    struct Square;
+
+    impl<T: Operand> Operation<T> for Square {
+        fn name(&self) -> &'static str {
+            "square"
+        }
+
+        fn symbol(&self) -> &'static str {
+            "sq"
+        }
+
+        fn arity(&self) -> Arity {
+            Arity::Exactly(1)
+        }
+
+        fn label(&self) -> &'static str {
+            "Square m1"
+        }
+
+        fn uses_policy(&self) -> bool {
+            true
+        }
+
+        fn evaluate(&self, args: &[T], policy: OverflowPolicy) -> Option<Result<(T, bool), CalcError>> {
+            Some(Calculator { m1: args[0], m2: args[0] }.multiply_with(policy))
+        }
+    }
+
+    #[test]
+    fn registers_after_the_builtins() {
+        let mut registry = Registry::<u8>::standard();
+        let builtins = registry.iter().count();
+        registry.register(Box::new(Square)).unwrap();
+
+        assert_eq!(registry.iter().count(), builtins + 1);
+        assert_eq!(registry.iter().last().map(|operation| operation.name()), Some("square"));
+        let square = registry.get("square").unwrap();
+        assert_eq!(apply(square, &Calculator { m1: 16, m2: 3 }, OverflowPolicy::Wrapping), Some(Ok((0, true))));
+        assert_eq!(apply(square, &Calculator { m1: 15, m2: 3 }, OverflowPolicy::Checked), Some(Ok((225, false))));
+    }
+
+    #[test]
+    fn is_not_a_standard_operation() {
+        assert!(Registry::<i64>::standard().get("square").is_none());
+    }
+}
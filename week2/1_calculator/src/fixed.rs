@@ -0,0 +1,419 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::arithmetic::{CalcError, OverflowPolicy};
+use crate::calculator::{AdditiveOperations, Calculator, MultiplicativeOperations};
+use crate::eval::{EvalError, Evaluation, Function, Step, Variables};
+use crate::expr::{BinaryOp, Expr};
+use crate::registry::Arity;
+
+/** FIXED POINT
+ * Unsigned fixed-point decimals stored as a raw u128 scaled by 10^decimals, the way on-chain code does
+ * scalar math: `collat as u128 * rate / DECIMALS_SCALAR`, `amount * total_supply / balance`
+ *  - `+` `-` work on the raw values under the selected overflow policy
+ *  - `*` is `a * b / scale` and `/` is `a * scale / b`, rounded with the selected Rounding
+ *  - `muldiv(a, b, d)` is `a * b / d` with a single rounding
+ *
+ * The intermediate product is a u128, as in the audited code, so it overflows exactly where they do,
+ * under the selected overflow policy like `+` and `-`
+ * Every rounded step reports the remainder that was dropped (or added when rounding up)
+ */
+pub const MAX_DECIMALS: u32 = 38;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+    Nearest,
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Rounding::Down => "down",
+            Rounding::Up => "up",
+            Rounding::Nearest => "nearest",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "down" | "floor" => Ok(Rounding::Down),
+            "up" | "ceil" => Ok(Rounding::Up),
+            "nearest" | "round" => Ok(Rounding::Nearest),
+            other => Err(format!("unknown rounding `{}`", other)),
+        }
+    }
+}
+
+// Result of a rounded division: `value` plus the part of the exact quotient it misses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rounded {
+    pub value: u128,
+    pub remainder: u128,
+    pub denominator: u128,
+    pub rounded_up: bool,
+}
+
+impl Rounded {
+    pub fn is_exact(&self) -> bool {
+        self.remainder == 0
+    }
+}
+
+// Precision lost, as a reduced fraction of the last raw unit
+impl fmt::Display for Rounded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_exact() {
+            return write!(f, "exact");
+        }
+        let (numerator, direction) = if self.rounded_up {
+            (self.denominator - self.remainder, "up")
+        } else {
+            (self.remainder, "down")
+        };
+        let divisor = gcd(numerator, self.denominator);
+        write!(
+            f,
+            "rounded {}, {}/{} of a unit {}",
+            direction,
+            numerator / divisor,
+            self.denominator / divisor,
+            if self.rounded_up { "added" } else { "lost" }
+        )
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+pub fn div_rounded(numerator: u128, denominator: u128, rounding: Rounding) -> Result<Rounded, CalcError> {
+    let quotient = Calculator { m1: numerator, m2: denominator }.divide()?;
+    let remainder = numerator - quotient * denominator;
+
+    //E nearest rounds half up: remainder >= denominator - remainder, written without overflowing
+    let round_up = remainder != 0
+        && match rounding {
+            Rounding::Down => false,
+            Rounding::Up => true,
+            Rounding::Nearest => remainder >= denominator - remainder,
+        };
+
+    let value = if round_up {
+        Calculator { m1: quotient, m2: 1 }.add()?
+    } else {
+        quotient
+    };
+
+    Ok(Rounded {
+        value,
+        remainder,
+        denominator,
+        rounded_up: round_up,
+    })
+}
+
+// a * b / denominator with a u128 intermediate product, the flag is set when the product overflowed
+pub fn mul_div(
+    a: u128,
+    b: u128,
+    denominator: u128,
+    rounding: Rounding,
+    policy: OverflowPolicy,
+) -> Result<(Rounded, bool), CalcError> {
+    let (product, overflowed) = Calculator { m1: a, m2: b }.multiply_with(policy)?;
+    Ok((div_rounded(product, denominator, rounding)?, overflowed))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedPoint {
+    pub decimals: u32,
+    pub rounding: Rounding,
+}
+
+impl FixedPoint {
+    pub fn new(decimals: u32, rounding: Rounding) -> Result<Self, String> {
+        if decimals > MAX_DECIMALS {
+            return Err(format!("at most {} decimals fit in a u128", MAX_DECIMALS));
+        }
+        Ok(Self { decimals, rounding })
+    }
+
+    pub fn scale(&self) -> u128 {
+        10u128.pow(self.decimals)
+    }
+
+    // "1.5" -> 1_500_000_000 with 9 decimals; more decimals than the scale holds is an error
+    pub fn parse(&self, literal: &str) -> Option<u128> {
+        let literal = literal.replace('_', "");
+        let (integer, fraction) = literal.split_once('.').unwrap_or((&literal, ""));
+        if fraction.len() > self.decimals as usize
+            || !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+            || integer.is_empty() && fraction.is_empty()
+        {
+            return None;
+        }
+
+        let integer: u128 = if integer.is_empty() { 0 } else { integer.parse().ok()? };
+        let padded = format!("{:0<width$}", fraction, width = self.decimals as usize);
+        let fraction: u128 = if padded.is_empty() { 0 } else { padded.parse().ok()? };
+        integer.checked_mul(self.scale())?.checked_add(fraction)
+    }
+
+    pub fn format(&self, raw: u128) -> String {
+        if self.decimals == 0 {
+            return raw.to_string();
+        }
+        let scale = self.scale();
+        format!("{}.{:0width$}", raw / scale, raw % scale, width = self.decimals as usize)
+    }
+
+    pub fn multiply(&self, a: u128, b: u128, policy: OverflowPolicy) -> Result<(Rounded, bool), CalcError> {
+        mul_div(a, b, self.scale(), self.rounding, policy)
+    }
+
+    pub fn divide(&self, a: u128, b: u128, policy: OverflowPolicy) -> Result<(Rounded, bool), CalcError> {
+        if b == 0 {
+            return Err(CalcError::DivisionByZero);
+        }
+        mul_div(a, self.scale(), b, self.rounding, policy)
+    }
+
+    pub fn evaluate(&self, expr: &Expr, vars: &Variables, policy: OverflowPolicy) -> Result<Evaluation, EvalError> {
+        let mut evaluator = FixedEvaluator {
+            fixed: *self,
+            vars,
+            policy,
+            steps: Vec::new(),
+        };
+        let value = evaluator.eval(expr)?;
+        Ok(Evaluation {
+            value: self.format(value),
+            overflowed: evaluator.steps.iter().any(|step| step.overflowed),
+            steps: evaluator.steps,
+        })
+    }
+}
+
+impl fmt::Display for FixedPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fixed({}, {})", self.decimals, self.rounding)
+    }
+}
+
+struct FixedEvaluator<'a> {
+    fixed: FixedPoint,
+    vars: &'a Variables,
+    policy: OverflowPolicy,
+    steps: Vec<Step>,
+}
+
+impl FixedEvaluator<'_> {
+    fn literal(&self, literal: &str) -> Result<u128, EvalError> {
+        self.fixed.parse(literal).ok_or_else(|| EvalError::InvalidLiteral {
+            literal: literal.to_string(),
+            type_name: "fixed-point decimal",
+        })
+    }
+
+    fn unsupported(operation: &'static str) -> EvalError {
+        EvalError::Unsupported {
+            operation,
+            type_name: "fixed-point decimals",
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<u128, EvalError> {
+        match expr {
+            Expr::Number(literal) => self.literal(literal),
+            Expr::Variable(name) => {
+                let value = self
+                    .vars
+                    .get(name)
+                    .ok_or_else(|| EvalError::UnknownVariable(name.clone()))?;
+                self.literal(value)
+            }
+            Expr::Unary(op, _) => Err(Self::unsupported(op.symbol())),
+            Expr::Call(name, args) if name == Function::MulDiv.name() => {
+                if args.len() != 3 {
+                    return Err(EvalError::Arity {
+                        function: Function::MulDiv.name(),
+                        expected: Arity::Exactly(3),
+                        found: args.len(),
+                    });
+                }
+                let (a, b, d) = (self.eval(&args[0])?, self.eval(&args[1])?, self.eval(&args[2])?);
+                //E muldiv scales like `*` then `/`, so the scale cancels out: a * b / d on the raw values
+                let (rounded, overflowed) = mul_div(a, b, d, self.fixed.rounding, self.policy)?;
+                let expression = format!(
+                    "muldiv({}, {}, {})",
+                    self.fixed.format(a),
+                    self.fixed.format(b),
+                    self.fixed.format(d)
+                );
+                self.record_rounded(expression, rounded, overflowed);
+                Ok(rounded.value)
+            }
+            Expr::Call(name, _) => match Function::ALL.iter().find(|function| function.name() == name) {
+                Some(function) => Err(Self::unsupported(function.name())),
+                None => Err(EvalError::UnknownFunction(name.clone())),
+            },
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                let expression = format!("{} {} {}", self.fixed.format(lhs), op.symbol(), self.fixed.format(rhs));
+                let calc = Calculator { m1: lhs, m2: rhs };
+
+                match op {
+                    BinaryOp::Add | BinaryOp::Sub => {
+                        let (value, overflowed) = if *op == BinaryOp::Add {
+                            calc.add_with(self.policy)?
+                        } else {
+                            calc.subtract_with(self.policy)?
+                        };
+                        self.steps.push(Step {
+                            expression,
+                            value: self.fixed.format(value),
+                            overflowed,
+                            note: None,
+                        });
+                        Ok(value)
+                    }
+                    BinaryOp::Mul => {
+                        let (rounded, overflowed) = self.fixed.multiply(lhs, rhs, self.policy)?;
+                        self.record_rounded(expression, rounded, overflowed);
+                        Ok(rounded.value)
+                    }
+                    BinaryOp::Div => {
+                        let (rounded, overflowed) = self.fixed.divide(lhs, rhs, self.policy)?;
+                        self.record_rounded(expression, rounded, overflowed);
+                        Ok(rounded.value)
+                    }
+                    _ => Err(Self::unsupported(op.symbol())),
+                }
+            }
+        }
+    }
+
+    fn record_rounded(&mut self, expression: String, rounded: Rounded, overflowed: bool) {
+        self.steps.push(Step {
+            expression,
+            value: self.fixed.format(rounded.value),
+            overflowed,
+            note: (!rounded.is_exact()).then(|| rounded.to_string()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{parse_statement, Statement};
+
+    fn fixed(decimals: u32, rounding: Rounding) -> FixedPoint {
+        FixedPoint::new(decimals, rounding).unwrap()
+    }
+
+    fn run(fixed: FixedPoint, policy: OverflowPolicy, input: &str) -> Result<Evaluation, EvalError> {
+        match parse_statement(input)? {
+            Statement::Expr(expr) | Statement::Assign(_, expr) => fixed.evaluate(&expr, &Variables::new(), policy),
+        }
+    }
+
+    fn value(fixed: FixedPoint, input: &str) -> String {
+        run(fixed, OverflowPolicy::Checked, input).unwrap().value
+    }
+
+    #[test]
+    fn rounds_divisions() {
+        let rounded = |rounding| div_rounded(10, 4, rounding).unwrap();
+        assert_eq!(rounded(Rounding::Down).value, 2);
+        assert_eq!(rounded(Rounding::Up).value, 3);
+        //E nearest rounds half up
+        assert_eq!(rounded(Rounding::Nearest).value, 3);
+        assert_eq!(div_rounded(9, 4, Rounding::Nearest).unwrap().value, 2);
+        let exact = Rounded { value: 2, remainder: 0, denominator: 4, rounded_up: false };
+        assert_eq!(div_rounded(8, 4, Rounding::Up), Ok(exact));
+        assert_eq!(div_rounded(1, 0, Rounding::Down), Err(CalcError::DivisionByZero));
+    }
+
+    #[test]
+    fn rounds_with_the_selected_mode() {
+        assert_eq!(value(fixed(2, Rounding::Down), "2 / 3"), "0.66");
+        assert_eq!(value(fixed(2, Rounding::Up), "2 / 3"), "0.67");
+        assert_eq!(value(fixed(2, Rounding::Nearest), "2 / 3"), "0.67");
+        assert_eq!(value(fixed(2, Rounding::Nearest), "1 / 3"), "0.33");
+        assert_eq!(value(fixed(1, Rounding::Up), "0.5 * 0.5"), "0.3");
+        assert_eq!(value(fixed(1, Rounding::Down), "muldiv(1, 1, 3)"), "0.3");
+        assert_eq!(value(fixed(1, Rounding::Up), "muldiv(1, 1, 3)"), "0.4");
+    }
+
+    #[test]
+    fn notes_the_precision_lost() {
+        let evaluation = run(fixed(2, Rounding::Down), OverflowPolicy::Checked, "2 / 3 + 1").unwrap();
+        assert_eq!(evaluation.steps[0].to_string(), "2.00 / 3.00 = 0.66 (rounded down, 2/3 of a unit lost)");
+        assert_eq!(evaluation.steps[1].note, None);
+
+        let evaluation = run(fixed(2, Rounding::Up), OverflowPolicy::Checked, "2 / 3").unwrap();
+        assert_eq!(evaluation.steps[0].note.as_deref(), Some("rounded up, 1/3 of a unit added"));
+
+        let evaluation = run(fixed(2, Rounding::Nearest), OverflowPolicy::Checked, "1.5 * 2").unwrap();
+        assert_eq!(evaluation.steps[0].note, None);
+    }
+
+    #[test]
+    fn has_no_negative_values() {
+        let checked = run(fixed(2, Rounding::Down), OverflowPolicy::Checked, "1 - 2");
+        assert!(matches!(checked, Err(EvalError::Calc(CalcError::Overflow { .. }))));
+        let saturating = run(fixed(2, Rounding::Down), OverflowPolicy::Saturating, "1 - 2").unwrap();
+        assert_eq!(saturating.value, "0.00");
+        assert!(saturating.overflowed);
+        assert!(matches!(
+            run(fixed(2, Rounding::Down), OverflowPolicy::Checked, "-1"),
+            Err(EvalError::Unsupported { operation: "-", .. })
+        ));
+        assert!(FixedPoint::new(2, Rounding::Down).unwrap().parse("-1").is_none());
+    }
+
+    #[test]
+    fn applies_the_policy_to_the_product() {
+        let big = "muldiv(340282366920938463463374607431768211455, 2, 4)";
+        assert!(matches!(
+            run(fixed(0, Rounding::Down), OverflowPolicy::Checked, big),
+            Err(EvalError::Calc(CalcError::Overflow { .. }))
+        ));
+
+        let wrapping = run(fixed(0, Rounding::Down), OverflowPolicy::Wrapping, big).unwrap();
+        assert_eq!(wrapping.value, ((u128::MAX - 1) / 4).to_string());
+        assert!(wrapping.overflowed);
+
+        let saturating = run(fixed(0, Rounding::Down), OverflowPolicy::Saturating, big).unwrap();
+        assert_eq!(saturating.value, (u128::MAX / 4).to_string());
+        assert!(saturating.overflowed);
+
+        assert!(mul_div(u128::MAX, 2, 4, Rounding::Down, OverflowPolicy::Overflowing).unwrap().1);
+        assert!(!mul_div(6, 7, 4, Rounding::Down, OverflowPolicy::Checked).unwrap().1);
+    }
+
+    #[test]
+    fn parses_and_formats() {
+        let fixed = fixed(3, Rounding::Down);
+        assert_eq!(fixed.parse("1.5"), Some(1500));
+        assert_eq!(fixed.parse(".25"), Some(250));
+        assert_eq!(fixed.parse("1_000"), Some(1_000_000));
+        assert_eq!(fixed.parse("1.2345"), None);
+        assert_eq!(fixed.parse("."), None);
+        assert_eq!(fixed.format(1500), "1.500");
+        assert_eq!(FixedPoint::new(0, Rounding::Down).unwrap().format(7), "7");
+        assert!(FixedPoint::new(MAX_DECIMALS + 1, Rounding::Down).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+
+use crate::arithmetic::OverflowPolicy;
+use crate::eval::{NumType, Variables};
+use crate::fixed::FixedPoint;
+
+/** HISTORY
+ * Every statement a Session evaluated, with the settings it ran under and what it produced
+ * A saved session is a plain script that could have been typed into the REPL:
+ *
+ *     # calculator session
+ *     :type u8
+ *     :policy wrapping
+ *     200 + 100
+ *     #= 44 overflowed
+ *
+ * Settings lines are only written when they change, and each `#=` line is the recorded result
+ * of the statement above it: loading the file replays every statement and fails on the first
+ * result that differs, so a saved session proves its numbers rather than just quoting them
+ */
+pub const SCRIPT_HEADER: &str = "# calculator session";
+pub const RESULT_PREFIX: &str = "#=";
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub input: String,
+    pub num_type: NumType,
+    pub fixed: Option<FixedPoint>,
+    pub policy: OverflowPolicy,
+    pub value: String,
+    pub overflowed: bool,
+    // variables before the statement ran, restored by undo
+    pub previous: Variables,
+}
+
+impl Entry {
+    // What the `#=` line records
+    pub fn result(&self) -> String {
+        if self.overflowed {
+            format!("{} overflowed", self.value)
+        } else {
+            self.value.clone()
+        }
+    }
+
+    fn mode(&self) -> String {
+        match self.fixed {
+            Some(fixed) => fixed.to_string(),
+            None => self.num_type.to_string(),
+        }
+    }
+
+    // The REPL command selecting this entry's operand type
+    fn type_command(&self) -> String {
+        match self.fixed {
+            Some(fixed) => format!(":fixed {} {}", fixed.decimals, fixed.rounding),
+            None => format!(":type {}", self.num_type),
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{} {}] {} => {}", self.mode(), self.policy, self.input, self.result())
+    }
+}
+
+pub fn to_script(entries: &[Entry]) -> String {
+    let mut lines = vec![SCRIPT_HEADER.to_string()];
+    let mut type_command = None;
+    let mut policy = None;
+
+    for entry in entries {
+        let command = entry.type_command();
+        if type_command.as_ref() != Some(&command) {
+            lines.push(command.clone());
+            type_command = Some(command);
+        }
+        if policy != Some(entry.policy) {
+            lines.push(format!(":policy {}", entry.policy));
+            policy = Some(entry.policy);
+        }
+        lines.push(entry.input.clone());
+        lines.push(format!("{} {}", RESULT_PREFIX, entry.result()));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Rounding;
+
+    fn entry(input: &str, num_type: NumType, policy: OverflowPolicy, value: &str, overflowed: bool) -> Entry {
+        Entry {
+            input: input.to_string(),
+            num_type,
+            fixed: None,
+            policy,
+            value: value.to_string(),
+            overflowed,
+            previous: Variables::new(),
+        }
+    }
+
+    #[test]
+    fn writes_settings_only_when_they_change() {
+        let entries = [
+            entry("200 + 100", NumType::U8, OverflowPolicy::Wrapping, "44", true),
+            entry("x = 1", NumType::U8, OverflowPolicy::Wrapping, "1", false),
+            entry("_ * 3", NumType::U8, OverflowPolicy::Checked, "3", false),
+            entry("-1", NumType::I64, OverflowPolicy::Checked, "-1", false),
+        ];
+        let expected = "\
+# calculator session
+:type u8
+:policy wrapping
+200 + 100
+#= 44 overflowed
+x = 1
+#= 1
+:policy checked
+_ * 3
+#= 3
+:type i64
+-1
+#= -1
+";
+        assert_eq!(to_script(&entries), expected);
+    }
+
+    #[test]
+    fn selects_fixed_point_with_its_rounding() {
+        let mut fixed = entry("1 / 3", NumType::I64, OverflowPolicy::Checked, "0.33", false);
+        fixed.fixed = Some(FixedPoint::new(2, Rounding::Nearest).unwrap());
+        let expected = "# calculator session\n:fixed 2 nearest\n:policy checked\n1 / 3\n#= 0.33\n";
+        assert_eq!(to_script(&[fixed.clone()]), expected);
+        assert_eq!(fixed.to_string(), "[fixed(2, nearest) checked] 1 / 3 => 0.33");
+    }
+
+    #[test]
+    fn writes_only_the_header_for_an_empty_history() {
+        assert_eq!(to_script(&[]), format!("{}\n", SCRIPT_HEADER));
+    }
+}
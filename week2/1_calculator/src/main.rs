@@ -1,107 +1,217 @@
-use std::fmt;
-
-#[derive(Debug)]
-struct Calculator {
-    m1: i32,
-    m2: i32,
-}
-
-// AdditiveOperations trait for addition and subtraction
-trait AdditiveOperations {
-    fn add(&self) -> i32;
-    fn subtract(&self) -> i32;
-}
-
-// MultiplicativeOperations trait for multiplication and division
-trait MultiplicativeOperations {
-    fn multiply(&self) -> i32;
-    fn divide(&self) -> Option<i32>; // Option to handle division by zero
-}
-
-// BinaryOperations trait for AND, OR, and XOR
-trait BinaryOperations {
-    fn bitwise_and(&self) -> i32;
-    fn bitwise_or(&self) -> i32;
-    fn bitwise_xor(&self) -> i32;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process::ExitCode;
+
+mod arithmetic;
+mod batch;
+mod bits;
+mod calculator;
+mod cast;
+mod eval;
+mod expr;
+mod extensions;
+mod fixed;
+mod history;
+mod registry;
+mod repl;
+mod report;
+mod u256;
+
+use arithmetic::OverflowPolicy;
+use calculator::{print_output, Calculator};
+use eval::NumType;
+use fixed::{FixedPoint, Rounding};
+use repl::Session;
+use report::Format;
+use u256::U256;
+
+const USAGE: &str = "\
+usage: calculator [OPTIONS] [EXPR]
+
+Evaluates EXPR (statements separated by `;`) and exits, or starts a REPL when no EXPR is given
+
+Options:
+  -t, --type <t>          operand type: i8..i128, u8..u256, f32, f64 (default i64)
+  -p, --policy <p>        overflow policy: checked, wrapping, saturating, overflowing (default checked)
+  -f, --fixed <d>         fixed-point decimals with d decimals instead of --type
+  -r, --rounding <r>      fixed-point rounding: down, up, nearest (default down)
+  -s, --steps             print intermediate values
+  -v, --var <name=value>  define a variable, may be repeated
+  -l, --load <file>       replay a saved session first, failing if any recorded result differs
+      --save <file>       save the session history when done
+  -b, --batch <file>      evaluate every `m1,m2` line of file (- for stdin), one CSV row per line
+      --ops <list>        batch operations, e.g. add,mul,shl (default add,sub,mul,div)
+      --list-ops          list the registered operations for --type
+      --report <m1> <m2>  print every Calculator operation on m1 and m2 for --type
+      --cast <value>      print value of --type cast to every integer type, with what each cast loses
+  -o, --format <f>        report and cast format: text, json, csv, markdown (default text)
+      --demo              print the Calculator demonstration
+  -h, --help              show this message
+      --                  end of options, the rest is the expression (e.g. `-- -x`)";
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-impl AdditiveOperations for Calculator {
-    fn add(&self) -> i32 {
-        self.m1 + self.m2
+fn run(args: Vec<String>) -> Result<ExitCode, String> {
+    let mut session = Session::new(NumType::I64, OverflowPolicy::Checked);
+    let mut expression: Vec<String> = Vec::new();
+    let mut vars: Vec<(String, String)> = Vec::new();
+    let mut decimals: Option<u32> = None;
+    let mut rounding = Rounding::Down;
+    let mut report: Option<(String, String)> = None;
+    let mut cast: Option<String> = None;
+    let mut format = Format::Text;
+    let mut num_type: Option<NumType> = None;
+    let mut policy: Option<OverflowPolicy> = None;
+    let mut batch: Option<String> = None;
+    let mut operations: Vec<String> = batch::DEFAULT_OPERATIONS.map(String::from).to_vec();
+    let mut list_operations = false;
+    let mut load: Option<String> = None;
+    let mut save: Option<String> = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("missing value for {}", flag));
+        match arg.as_str() {
+            "-t" | "--type" => num_type = Some(value(&arg)?.parse()?),
+            "-p" | "--policy" => policy = Some(value(&arg)?.parse()?),
+            "-f" | "--fixed" => {
+                let value = value(&arg)?;
+                decimals = Some(value.parse().map_err(|_| format!("invalid decimals `{}`", value))?);
+            }
+            "-r" | "--rounding" => rounding = value(&arg)?.parse()?,
+            "-s" | "--steps" => session.show_steps = true,
+            "-l" | "--load" => load = Some(value(&arg)?),
+            "--save" => save = Some(value(&arg)?),
+            "-b" | "--batch" => batch = Some(value(&arg)?),
+            "--ops" => operations = batch::parse_names(&value(&arg)?),
+            "--list-ops" => list_operations = true,
+            "--report" => report = Some((value(&arg)?, value(&arg)?)),
+            "--cast" => cast = Some(value(&arg)?),
+            "-o" | "--format" => format = value(&arg)?.parse()?,
+            "-v" | "--var" => {
+                let definition = value(&arg)?;
+                let (name, value) = definition
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected name=value, got `{}`", definition))?;
+                vars.push((name.trim().to_string(), value.trim().to_string()));
+            }
+            "--demo" => {
+                demo();
+                return Ok(ExitCode::SUCCESS);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(ExitCode::SUCCESS);
+            }
+            //E everything after `--` is expression, so `-- -x` negates the variable x
+            "--" => {
+                expression.extend(args);
+                break;
+            }
+            //E a dash before anything but a letter starts an expression such as `-5 + 3` or `-(1)`, not a flag
+            flag if flag.starts_with('-') && flag[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '-') => {
+                return Err(format!("unknown option `{}`", flag));
+            }
+            _ => expression.push(arg),
+        }
     }
 
-    fn subtract(&self) -> i32 {
-        self.m1 - self.m2
+    //E a loaded session brings its own settings, the ones given on the command line apply from there on
+    if let Some(path) = load {
+        session.load(&path)?;
+    }
+    if let Some(num_type) = num_type {
+        session.num_type = num_type;
+        session.fixed = None;
+    }
+    if let Some(policy) = policy {
+        session.policy = policy;
     }
-    
-}
 
-impl MultiplicativeOperations for Calculator {
-    fn multiply(&self) -> i32 {
-        self.m1 * self.m2
+    if list_operations {
+        println!("{}", repl::operation_table(session.num_type));
+        return Ok(ExitCode::SUCCESS);
     }
 
-    fn divide(&self) -> Option<i32> {
-        if self.m2 == 0 {
-            None
+    if let Some(path) = batch {
+        if decimals.is_some() {
+            return Err("--batch needs an integer or float --type, not --fixed".to_string());
+        }
+        let input: Box<dyn io::BufRead> = if path == "-" {
+            Box::new(io::stdin().lock())
         } else {
-            Some(self.m1 / self.m2)
+            Box::new(BufReader::new(File::open(&path).map_err(|err| format!("cannot read {}: {}", path, err))?))
+        };
+        let summary = batch::run(input, io::stdout().lock(), session.num_type, session.policy, &operations)?;
+        if summary.failed > 0 {
+            eprintln!("{} of {} rows could not be evaluated", summary.failed, summary.rows);
+            return Ok(ExitCode::FAILURE);
         }
+        return Ok(ExitCode::SUCCESS);
     }
-}
 
-impl BinaryOperations for Calculator {
-    fn bitwise_and(&self) -> i32 {
-        self.m1 & self.m2
+    if let Some((m1, m2)) = report {
+        if decimals.is_some() {
+            return Err("--report needs an integer or float --type, not --fixed".to_string());
+        }
+        let report = session.num_type.report(&m1, &m2).map_err(|err| err.to_string())?;
+        println!("{}", report.render(format));
+        return Ok(ExitCode::SUCCESS);
     }
 
-    fn bitwise_or(&self) -> i32 {
-        self.m1 | self.m2
+    if let Some(value) = cast {
+        if decimals.is_some() {
+            return Err("--cast needs an integer or float --type, not --fixed".to_string());
+        }
+        let casts = session.num_type.casts(&value).map_err(|err| err.to_string())?;
+        println!("{}", casts.render(format));
+        return Ok(ExitCode::SUCCESS);
     }
 
-    fn bitwise_xor(&self) -> i32 {
-        self.m1 ^ self.m2
+    if let Some(decimals) = decimals {
+        session.fixed = Some(FixedPoint::new(decimals, rounding)?);
     }
-}
-
 
+    //E variables go through the evaluator so `--var x=0xff` is checked against the selected type
+    for (name, value) in vars {
+        session
+            .run_statement(&format!("{} = {}", name, value))
+            .map_err(|err| format!("--var {}: {}", name, err))?;
+    }
 
-impl fmt::Display for Calculator {
-
-    //E required method for implementing the Display trait in Rust
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-
-        let division_result = match self.divide() {
-            Some(result) => result.to_string(),
-            None => "Division by zero".to_string(),
-        };
-
-        write!(
-            f,
-            "Calculator({}, {}):\n\
-             Addition: {}\n\
-             Subtraction: {}\n\
-             Multiplication: {}\n\
-             Division: {}\n\
-             AND: {}\n\
-             OR: {}\n\
-             XOR: {}",
-            self.m1,
-            self.m2,
-            self.add(),
-            self.subtract(),
-            self.multiply(),
-            division_result,
-            self.bitwise_and(),
-            self.bitwise_or(),
-            self.bitwise_xor()
-        )
+    let mut code = ExitCode::SUCCESS;
+    if expression.is_empty() {
+        repl::run(&mut session).map_err(|err| err.to_string())?;
+    } else {
+        for statement in expression.join(" ").split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            match session.run_statement(statement) {
+                Ok(evaluation) => println!("{}", session.render(&evaluation)),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    code = ExitCode::FAILURE;
+                    break;
+                }
+            }
+        }
+    }
 
+    //E statements that ran before an error are still saved, the failing one is not in the history
+    if let Some(path) = save {
+        session.save(&path)?;
     }
+    Ok(code)
 }
 
-
-fn main() {
+fn demo() {
     // Create a new Calculator instance
     let calculator = Calculator {
         m1: 10,
@@ -116,32 +226,29 @@ fn main() {
         m1: 7,
         m2: 0,
     };
-    
+
     println!("\ncalculator with division by zero: {}", calc_div_zero);
 
+    // Same operands at different widths and signedness
+    println!("\ncalculator u8: {}", Calculator { m1: 12u8, m2: 10u8 });
+
+    // Overflow cases: u8 and u128 wrap-around, i8::MIN / -1, f32 infinity
+    println!("\ncalculator u8 overflow: {}", Calculator { m1: 200u8, m2: 100u8 });
+    println!("\ncalculator u128 overflow: {}", Calculator { m1: u128::MAX, m2: 2u128 });
+    println!("\ncalculator i8 MIN / -1: {}", Calculator { m1: i8::MIN, m2: -1i8 });
+    println!("\ncalculator f32 overflow: {}", Calculator { m1: f32::MAX, m2: 2.0f32 });
+
+    // Cairo u256: 2^128 + 5 only keeps 5 once truncated to its `low` limb
+    let amount: U256 = "340282366920938463463374607431768211461".parse().expect("valid u256 literal");
+    println!("\ncalculator u256: {}", Calculator { m1: amount, m2: U256::from(3) });
+    println!("amount = {:#x}, amount.low = {}, amount.high = {}", amount, amount.low(), amount.high());
+    println!("\ncalculator i128: {}", Calculator { m1: -7i128, m2: 3i128 });
+    println!("\ncalculator f64: {}", Calculator { m1: 7.0f64, m2: 3.0f64 });
+
     let calc_2 = Calculator {
-        m1: 7,
-        m2: 3,
+        m1: 7u64,
+        m2: 3u64,
     };
     println!("\nUsing print_output function with division by zero:");
     print_output(&calc_2);
 }
-
-
-fn print_output<T>(calc: &T) where T: AdditiveOperations + MultiplicativeOperations + BinaryOperations {
-    
-    println!("Addition: {}", calc.add());
-    println!("Subtraction: {}", calc.subtract());
-    println!("Multiplication: {}", calc.multiply());
-
-    let division_result = match calc.divide() {
-        Some(result) => result.to_string(),
-        None => "Division by zero".to_string(),
-    };
-    println!("Division: {}", division_result);
-
-    println!("AND: {}", calc.bitwise_and());
-    println!("OR: {}", calc.bitwise_or());
-    println!("XOR: {}", calc.bitwise_xor());
-
-}
\ No newline at end of file
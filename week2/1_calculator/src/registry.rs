@@ -0,0 +1,377 @@
+use std::fmt;
+
+use crate::arithmetic::{CalcError, OverflowPolicy};
+use crate::calculator::{
+    AdditiveOperations, Calculator, MultiplicativeOperations, PowerOperations, ReductionOperations, UnaryOperations,
+};
+use crate::eval::{Function, Operand};
+use crate::expr::BinaryOp;
+use crate::extensions;
+
+// Number of operands an operation takes: `Exactly(1)` for m1 only, `AtLeast(1)` for a reduction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Exactly(arity) => count == arity,
+            Arity::AtLeast(minimum) => count >= minimum,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exactly(arity) => write!(f, "{}", arity),
+            Arity::AtLeast(minimum) => write!(f, "{}+", minimum),
+        }
+    }
+}
+
+/** OPERATION
+ * A named operation the Calculator Display, the report, batch mode and expressions can run
+ *  - `arity` is the number of operands it accepts
+ *  - `evaluate` gets a number of operands `arity` accepts and returns None when the operand type has
+ *    no such operation (bit operations on floats), the flag is true when the policy absorbed an overflow
+ *
+ * Operations that honour the overflow policy are shown once per policy, the others once
+ */
+pub trait Operation<T> {
+    fn name(&self) -> &'static str;
+    fn symbol(&self) -> &'static str;
+    fn arity(&self) -> Arity;
+
+    // Row label in the Calculator Display and the report
+    fn label(&self) -> &'static str {
+        self.name()
+    }
+
+    fn uses_policy(&self) -> bool {
+        false
+    }
+
+    fn evaluate(&self, args: &[T], policy: OverflowPolicy) -> Option<Result<(T, bool), CalcError>>;
+}
+
+// One of the `*_with` operations of the Calculator
+type PolicyOperation<T> = fn(&Calculator<T>, OverflowPolicy) -> Result<(T, bool), CalcError>;
+
+// One of the `*_with` operations of ReductionOperations
+type ReduceOperation<T> = fn(&[T], OverflowPolicy) -> Result<(T, bool), CalcError>;
+
+// Built-in operations are plain functions, over the Calculator traits or the Operand bit operations
+//E a unary policy operation still runs on a Calculator, with m2 = 0
+enum Evaluate<T> {
+    Policy(PolicyOperation<T>),
+    Reduce(ReduceOperation<T>),
+    Plain(fn(&[T]) -> Option<Result<T, CalcError>>),
+}
+
+struct Builtin<T> {
+    name: &'static str,
+    symbol: &'static str,
+    label: &'static str,
+    arity: Arity,
+    evaluate: Evaluate<T>,
+}
+
+impl<T: Operand> Builtin<T> {
+    fn policy(
+        name: &'static str,
+        symbol: &'static str,
+        label: &'static str,
+        evaluate: PolicyOperation<T>,
+    ) -> Self {
+        Self {
+            name,
+            symbol,
+            label,
+            arity: Arity::Exactly(2),
+            evaluate: Evaluate::Policy(evaluate),
+        }
+    }
+
+    fn unary(
+        name: &'static str,
+        symbol: &'static str,
+        label: &'static str,
+        evaluate: PolicyOperation<T>,
+    ) -> Self {
+        Self {
+            name,
+            symbol,
+            label,
+            arity: Arity::Exactly(1),
+            evaluate: Evaluate::Policy(evaluate),
+        }
+    }
+
+    fn reduce(
+        name: &'static str,
+        symbol: &'static str,
+        label: &'static str,
+        evaluate: ReduceOperation<T>,
+    ) -> Self {
+        Self {
+            name,
+            symbol,
+            label,
+            arity: Arity::AtLeast(1),
+            evaluate: Evaluate::Reduce(evaluate),
+        }
+    }
+
+    fn plain(
+        name: &'static str,
+        symbol: &'static str,
+        label: &'static str,
+        arity: Arity,
+        evaluate: fn(&[T]) -> Option<Result<T, CalcError>>,
+    ) -> Self {
+        Self {
+            name,
+            symbol,
+            label,
+            arity,
+            evaluate: Evaluate::Plain(evaluate),
+        }
+    }
+}
+
+impl<T: Operand> Operation<T> for Builtin<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn symbol(&self) -> &'static str {
+        self.symbol
+    }
+
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    fn label(&self) -> &'static str {
+        self.label
+    }
+
+    fn uses_policy(&self) -> bool {
+        matches!(self.evaluate, Evaluate::Policy(_) | Evaluate::Reduce(_))
+    }
+
+    fn evaluate(&self, args: &[T], policy: OverflowPolicy) -> Option<Result<(T, bool), CalcError>> {
+        match self.evaluate {
+            Evaluate::Policy(evaluate) => {
+                let m2 = args.get(1).copied().unwrap_or_else(T::zero);
+                Some(evaluate(&Calculator { m1: args[0], m2 }, policy))
+            }
+            Evaluate::Reduce(evaluate) => Some(evaluate(args, policy)),
+            Evaluate::Plain(evaluate) => evaluate(args).map(|result| result.map(|value| (value, false))),
+        }
+    }
+}
+
+fn bitwise<T: Operand>(op: BinaryOp, args: &[T]) -> Option<Result<T, CalcError>> {
+    T::bitwise(op, &Calculator { m1: args[0], m2: args[1] })
+}
+
+fn unary<T: Operand>(args: &[T]) -> Calculator<T> {
+    Calculator { m1: args[0], m2: T::zero() }
+}
+
+/** REGISTRY
+ * The operations for one operand type, in the order they are displayed
+ * `standard()` holds the built-in Calculator operations followed by the ones from `extensions`
+ */
+pub struct Registry<T> {
+    operations: Vec<Box<dyn Operation<T>>>,
+}
+
+impl<T: Operand> Registry<T> {
+    pub fn new() -> Self {
+        Self { operations: Vec::new() }
+    }
+
+    pub fn standard() -> Self {
+        let builtins: Vec<Builtin<T>> = vec![
+            Builtin::policy("add", "+", "Addition", |calc, policy| calc.add_with(policy)),
+            Builtin::policy("sub", "-", "Subtraction", |calc, policy| calc.subtract_with(policy)),
+            Builtin::policy("mul", "*", "Multiplication", |calc, policy| calc.multiply_with(policy)),
+            Builtin::policy("div", "/", "Division", |calc, policy| calc.divide_with(policy)),
+            Builtin::policy("pow", "**", "Power", |calc, policy| calc.power_with(policy)),
+            Builtin::unary("neg", "neg", "Negation m1", |calc, policy| calc.negate_with(policy)),
+            Builtin::unary("abs", "abs", "Absolute m1", |calc, policy| calc.abs_with(policy)),
+            Builtin::reduce("sum", "sum", "Sum", |args, policy| args.sum_with(policy)),
+            Builtin::reduce("product", "product", "Product", |args, policy| args.product_with(policy)),
+            Builtin::reduce("mean", "mean", "Mean", |args, policy| args.mean_with(policy)),
+            Builtin::plain("and", "&", "AND", Arity::Exactly(2), |args| bitwise(BinaryOp::And, args)),
+            Builtin::plain("or", "|", "OR", Arity::Exactly(2), |args| bitwise(BinaryOp::Or, args)),
+            Builtin::plain("xor", "^", "XOR", Arity::Exactly(2), |args| bitwise(BinaryOp::Xor, args)),
+            Builtin::plain("not", "!", "NOT m1", Arity::Exactly(1), |args| args[0].not().map(Ok)),
+            Builtin::plain("shl", "<<", "SHL", Arity::Exactly(2), |args| bitwise(BinaryOp::Shl, args)),
+            Builtin::plain("shr", ">>", "SHR", Arity::Exactly(2), |args| bitwise(BinaryOp::Shr, args)),
+            Builtin::plain("lshr", ">>>", "SHR (logical)", Arity::Exactly(2), |args| bitwise(BinaryOp::LogicalShr, args)),
+            Builtin::plain("rotl", "rotl", "ROTL", Arity::Exactly(2), |args| T::function(Function::RotateLeft, args)),
+            Builtin::plain("rotr", "rotr", "ROTR", Arity::Exactly(2), |args| T::function(Function::RotateRight, args)),
+            Builtin::plain("popcount", "popcount", "Popcount m1", Arity::Exactly(1), |args| T::function(Function::PopCount, args)),
+            Builtin::plain("clz", "clz", "Leading zeros m1", Arity::Exactly(1), |args| T::function(Function::LeadingZeros, args)),
+            Builtin::plain("ctz", "ctz", "Trailing zeros m1", Arity::Exactly(1), |args| T::function(Function::TrailingZeros, args)),
+            Builtin::plain("min", "min", "Min", Arity::AtLeast(1), |args| Some(args.minimum())),
+            Builtin::plain("max", "max", "Max", Arity::AtLeast(1), |args| Some(args.maximum())),
+            Builtin::plain("sqrt", "sqrt", "Square root m1", Arity::Exactly(1), |args| Some(unary(args).sqrt())),
+            Builtin::plain("log2", "log2", "Log2 m1", Arity::Exactly(1), |args| Some(unary(args).log2())),
+            Builtin::plain("log10", "log10", "Log10 m1", Arity::Exactly(1), |args| Some(unary(args).log10())),
+        ];
+
+        let mut registry = Self::new();
+        for builtin in builtins {
+            registry.register(Box::new(builtin)).expect("built-in names are unique");
+        }
+        extensions::register(&mut registry);
+        registry
+    }
+
+    // Names are unique: a second operation under the same name is rejected
+    pub fn register(&mut self, operation: Box<dyn Operation<T>>) -> Result<(), String> {
+        if self.get(operation.name()).is_some() {
+            return Err(format!("operation `{}` is already registered", operation.name()));
+        }
+        self.operations.push(operation);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Operation<T>> {
+        self.iter().find(|operation| operation.name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Operation<T>> {
+        self.operations.iter().map(|operation| operation.as_ref())
+    }
+
+    // "add,mul,shl" -> the matching operations, in the order given
+    pub fn select(&self, names: &[String]) -> Result<Vec<&dyn Operation<T>>, String> {
+        names
+            .iter()
+            .map(|name| {
+                self.get(name).ok_or_else(|| {
+                    let known: Vec<&str> = self.iter().map(|operation| operation.name()).collect();
+                    format!("unknown operation `{}`, expected one of {}", name, known.join(", "))
+                })
+            })
+            .collect()
+    }
+}
+
+// Runs an operation on the two Calculator operands, unary operations only get m1
+// and a reduction gets both, e.g. the mean of m1 and m2
+pub fn apply<T: Operand>(
+    operation: &dyn Operation<T>,
+    calc: &Calculator<T>,
+    policy: OverflowPolicy,
+) -> Option<Result<(T, bool), CalcError>> {
+    let args = [calc.m1, calc.m2];
+    match operation.arity() {
+        Arity::Exactly(arity @ (1 | 2)) => operation.evaluate(&args[..arity], policy),
+        arity if arity.accepts(2) => operation.evaluate(&args, policy),
+        _ => None,
+    }
+}
+
+// name, symbol, arity and label of every registered operation, for `--list-ops` and `:ops`
+pub fn describe<T: Operand>() -> Vec<[String; 4]> {
+    Registry::<T>::standard()
+        .iter()
+        .map(|operation| {
+            [
+                operation.name().to_string(),
+                operation.symbol().to_string(),
+                operation.arity().to_string(),
+                operation.label().to_string(),
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{EvalError, NumType, Variables};
+    use crate::expr::{parse_statement, Statement};
+
+    fn names(operations: &[&dyn Operation<i32>]) -> Vec<&'static str> {
+        operations.iter().map(|operation| operation.name()).collect()
+    }
+
+    fn call(input: &str) -> Result<String, EvalError> {
+        let Statement::Expr(expr) = parse_statement(input)? else {
+            panic!("`{}` is not an expression", input);
+        };
+        NumType::I32.evaluate(&expr, &Variables::new(), OverflowPolicy::Checked).map(|evaluation| evaluation.value)
+    }
+
+    #[test]
+    fn checks_arity() {
+        assert!(Arity::Exactly(2).accepts(2));
+        assert!(!Arity::Exactly(2).accepts(1));
+        assert!(Arity::AtLeast(1).accepts(5));
+        assert!(!Arity::AtLeast(1).accepts(0));
+        assert_eq!(Arity::Exactly(2).to_string(), "2");
+        assert_eq!(Arity::AtLeast(1).to_string(), "1+");
+    }
+
+    #[test]
+    fn registers_in_order() {
+        let mut registry = Registry::<i32>::new();
+        let add = Builtin::policy("add", "+", "Addition", |calc, policy| calc.add_with(policy));
+        let neg = Builtin::unary("neg", "neg", "Negation m1", |calc, policy| calc.negate_with(policy));
+        registry.register(Box::new(add)).unwrap();
+        registry.register(Box::new(neg)).unwrap();
+        assert_eq!(names(&registry.iter().collect::<Vec<_>>()), ["add", "neg"]);
+        assert!(registry.get("add").unwrap().uses_policy());
+        assert!(registry.get("sub").is_none());
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let mut registry = Registry::<i32>::standard();
+        let duplicate = Builtin::plain("add", "+", "Other addition", Arity::Exactly(2), |_| None);
+        assert_eq!(registry.register(Box::new(duplicate)), Err("operation `add` is already registered".to_string()));
+        assert_eq!(registry.get("add").unwrap().label(), "Addition");
+    }
+
+    #[test]
+    fn selects_by_name() {
+        let registry = Registry::<i32>::standard();
+        let selected = registry.select(&["shl".to_string(), "add".to_string()]).unwrap();
+        assert_eq!(names(&selected), ["shl", "add"]);
+
+        let err = registry.select(&["add".to_string(), "nope".to_string()]).err().unwrap();
+        assert!(err.starts_with("unknown operation `nope`, expected one of add, sub, mul"));
+    }
+
+    #[test]
+    fn applies_operations_to_the_operands() {
+        let registry = Registry::<i32>::standard();
+        let calc = Calculator { m1: 7, m2: 3 };
+        let apply = |name| apply(registry.get(name).unwrap(), &calc, OverflowPolicy::Checked);
+        assert_eq!(apply("sub"), Some(Ok((4, false))));
+        assert_eq!(apply("neg"), Some(Ok((-7, false))));
+        assert_eq!(apply("mean"), Some(Ok((5, false))));
+        let float_shift = Registry::<f64>::standard();
+        assert_eq!(float_shift.get("shl").unwrap().evaluate(&[1.0, 2.0], OverflowPolicy::Checked), None);
+    }
+
+    #[test]
+    fn checks_arity_of_calls() {
+        assert_eq!(call("neg(5)").unwrap(), "-5");
+        assert_eq!(call("sum(1, 2, 3, 4)").unwrap(), "10");
+        assert_eq!(call("neg(1, 2)"), Err(EvalError::Arity { function: "neg", expected: Arity::Exactly(1), found: 2 }));
+        assert_eq!(call("sum()"), Err(EvalError::Arity { function: "sum", expected: Arity::AtLeast(1), found: 0 }));
+        assert_eq!(call("square(3)"), Err(EvalError::UnknownFunction("square".to_string())));
+    }
+}
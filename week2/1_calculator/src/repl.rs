@@ -0,0 +1,442 @@
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::arithmetic::OverflowPolicy;
+use crate::eval::{EvalError, Evaluation, Function, NumType, Variables};
+use crate::expr::{parse_statement, Statement};
+use crate::fixed::{FixedPoint, Rounding};
+use crate::history::{self, Entry, RESULT_PREFIX};
+use crate::report::Format;
+
+const HELP: &str = "\
+Enter an expression such as `(a + b) * c / d ^ e & f`, or assign one with `name = expr`
+The last result is stored in `_`
+Operators: + - * / ** & | ^ << >> >>> and unary - !
+Commands:
+  :type <t>       operand type: i8..i128, u8..u256, f32, f64
+  :policy <p>     overflow policy: checked, wrapping, saturating, overflowing
+  :fixed <d> [r]  fixed-point decimals with d decimals, rounding r: down, up, nearest
+  :fixed off      back to plain integer / float operands
+  :steps          toggle printing of intermediate values
+  :report <a> <b> every Calculator operation on a and b
+  :cast <a>       a cast to every integer type, with what each cast loses
+  :format <f>     report and cast format: text, json, csv, markdown
+  :ops            list the registered operations, callable as functions
+  :vars           list variables
+  :history        list evaluated statements
+  :undo / :redo   take back the last statement / evaluate it again
+  :save <file>    save the history as a replayable session script
+  :load <file>    replay a saved session, checking every recorded result
+  :clear          remove every variable
+  :help           show this message
+  :quit           exit";
+
+// State shared by the REPL and the one-shot CLI mode
+pub struct Session {
+    pub num_type: NumType,
+    pub policy: OverflowPolicy,
+    // when set, expressions are evaluated as fixed-point decimals instead of `num_type`
+    pub fixed: Option<FixedPoint>,
+    pub vars: Variables,
+    pub show_steps: bool,
+    pub format: Format,
+    pub history: Vec<Entry>,
+    // entries taken back by :undo, most recent last; any new statement clears them
+    undone: Vec<Entry>,
+}
+
+impl Session {
+    pub fn new(num_type: NumType, policy: OverflowPolicy) -> Self {
+        Self {
+            num_type,
+            policy,
+            fixed: None,
+            vars: Variables::new(),
+            show_steps: false,
+            format: Format::Text,
+            history: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    // Evaluates `expr` or `name = expr`; the result is also stored in `_`
+    pub fn run_statement(&mut self, input: &str) -> Result<Evaluation, EvalError> {
+        let evaluation = self.execute(input, self.num_type, self.fixed, self.policy)?;
+        self.undone.clear();
+        Ok(evaluation)
+    }
+
+    // Runs a statement under the given settings and records it in the history
+    fn execute(
+        &mut self,
+        input: &str,
+        num_type: NumType,
+        fixed: Option<FixedPoint>,
+        policy: OverflowPolicy,
+    ) -> Result<Evaluation, EvalError> {
+        let (name, expr) = match parse_statement(input)? {
+            Statement::Assign(name, expr) => (Some(name), expr),
+            Statement::Expr(expr) => (None, expr),
+        };
+
+        let evaluation = match fixed {
+            Some(fixed) => fixed.evaluate(&expr, &self.vars, policy)?,
+            None => num_type.evaluate(&expr, &self.vars, policy)?,
+        };
+        let previous = self.vars.clone();
+        if let Some(name) = name {
+            self.vars.insert(name, evaluation.value.clone());
+        }
+        self.vars.insert("_".to_string(), evaluation.value.clone());
+
+        self.history.push(Entry {
+            input: input.to_string(),
+            num_type,
+            fixed,
+            policy,
+            value: evaluation.value.clone(),
+            overflowed: evaluation.overflowed,
+            previous,
+        });
+        Ok(evaluation)
+    }
+
+    // Takes back the last statement, restoring the variables it changed
+    pub fn undo(&mut self) -> Option<&Entry> {
+        let entry = self.history.pop()?;
+        self.vars = entry.previous.clone();
+        self.undone.push(entry);
+        self.undone.last()
+    }
+
+    // Evaluates the last undone statement again, under the settings it first ran with
+    pub fn redo(&mut self) -> Option<Result<Evaluation, EvalError>> {
+        let entry = self.undone.pop()?;
+        let result = self.execute(&entry.input, entry.num_type, entry.fixed, entry.policy);
+        if result.is_err() {
+            self.undone.push(entry);
+        }
+        Some(result)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, history::to_script(&self.history)).map_err(|err| format!("cannot write {}: {}", path, err))
+    }
+
+    // Replays a saved session into a fresh one, which replaces this session once every result matched
+    pub fn load(&mut self, path: &str) -> Result<usize, String> {
+        let script = fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?;
+        let mut session = Session::new(NumType::I64, OverflowPolicy::Checked);
+        session.show_steps = self.show_steps;
+        session.format = self.format;
+        session.replay(&script).map_err(|err| format!("{}: {}", path, err))?;
+
+        let statements = session.history.len();
+        *self = session;
+        Ok(statements)
+    }
+
+    fn replay(&mut self, script: &str) -> Result<(), String> {
+        for (index, line) in script.lines().enumerate() {
+            let line = line.trim();
+            let at = |err: String| format!("line {}: {}", index + 1, err);
+
+            if let Some(expected) = line.strip_prefix(RESULT_PREFIX) {
+                let entry = self.history.last().ok_or_else(|| at("result without a statement".to_string()))?;
+                let (expected, found) = (expected.trim(), entry.result());
+                if expected != found {
+                    return Err(at(format!("`{}` recorded `{}` but replays to `{}`", entry.input, expected, found)));
+                }
+            } else if line.is_empty() || line.starts_with('#') {
+                continue;
+            } else if line.starts_with(':') {
+                //E only settings may appear in a script, :load / :save there would not replay deterministically
+                let command = line.split_whitespace().next().unwrap_or("");
+                if !matches!(command, ":type" | ":fixed" | ":policy") {
+                    return Err(at(format!("`{}` is not allowed in a session script", command)));
+                }
+                self.command(line).map_err(at)?;
+            } else {
+                self.run_statement(line).map_err(|err| at(err.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Renders an evaluation the way the REPL prints it
+    pub fn render(&self, evaluation: &Evaluation) -> String {
+        let mut out = String::new();
+        //E steps with a note (precision lost) are always shown, the others only with :steps
+        for step in &evaluation.steps {
+            if self.show_steps || step.note.is_some() {
+                out += &format!("  {}\n", step);
+            }
+        }
+        out += &evaluation.value;
+        if evaluation.overflowed {
+            out += &format!("  (overflowed, {} result)", self.policy);
+        }
+        out
+    }
+
+    // Operand type shown in the prompt
+    pub fn mode(&self) -> String {
+        match self.fixed {
+            Some(fixed) => fixed.to_string(),
+            None => self.num_type.to_string(),
+        }
+    }
+
+    // `:command` lines; Ok(None) means quit
+    fn command(&mut self, line: &str) -> Result<Option<String>, String> {
+        let mut parts = line.split_whitespace();
+        let name = parts.next().unwrap_or("");
+        let arg = parts.next();
+
+        let output = match (name, arg) {
+            (":help", _) => {
+                let functions: Vec<&str> = Function::ALL.iter().map(|function| function.signature()).collect();
+                format!("{}\nFunctions: {}", HELP, functions.join(" "))
+            }
+            (":quit" | ":q" | ":exit", _) => return Ok(None),
+            (":type", Some(arg)) => {
+                self.num_type = arg.parse()?;
+                self.fixed = None;
+                format!("type: {}", self.num_type)
+            }
+            (":type", None) => format!("type: {}", self.mode()),
+            (":fixed", Some("off")) => {
+                self.fixed = None;
+                format!("type: {}", self.num_type)
+            }
+            (":fixed", Some(decimals)) => {
+                let decimals = decimals.parse().map_err(|_| format!("invalid decimals `{}`", decimals))?;
+                let rounding = match parts.next() {
+                    Some(rounding) => rounding.parse()?,
+                    None => Rounding::Down,
+                };
+                self.fixed = Some(FixedPoint::new(decimals, rounding)?);
+                format!("type: {}", self.mode())
+            }
+            (":fixed", None) => format!("type: {}", self.mode()),
+            (":policy", Some(arg)) => {
+                self.policy = arg.parse()?;
+                format!("policy: {}", self.policy)
+            }
+            (":policy", None) => format!("policy: {}", self.policy),
+            (":steps", _) => {
+                self.show_steps = !self.show_steps;
+                format!("steps: {}", if self.show_steps { "on" } else { "off" })
+            }
+            (":format", Some(arg)) => {
+                self.format = arg.parse()?;
+                format!("format: {}", self.format)
+            }
+            (":format", None) => format!("format: {}", self.format),
+            (":report", Some(m1)) => {
+                let m2 = parts.next().ok_or("usage: :report <a> <b>")?;
+                if self.fixed.is_some() {
+                    return Err("reports need an integer or float :type, not fixed-point".to_string());
+                }
+                let m1 = self.vars.get(m1).map_or(m1, String::as_str);
+                let m2 = self.vars.get(m2).map_or(m2, String::as_str);
+                let report = self.num_type.report(m1, m2).map_err(|err| err.to_string())?;
+                report.render(self.format)
+            }
+            (":cast", Some(value)) => {
+                if self.fixed.is_some() {
+                    return Err("casts need an integer or float :type, not fixed-point".to_string());
+                }
+                let value = self.vars.get(value).map_or(value, String::as_str);
+                let casts = self.num_type.casts(value).map_err(|err| err.to_string())?;
+                casts.render(self.format)
+            }
+            (":history", _) => self
+                .history
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| format!("{:>4}  {}", index + 1, entry))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            (":undo", _) => match self.undo() {
+                Some(entry) => format!("undone: {}", entry.input),
+                None => return Err("nothing to undo".to_string()),
+            },
+            (":redo", _) => match self.redo() {
+                Some(result) => self.render(&result.map_err(|err| err.to_string())?),
+                None => return Err("nothing to redo".to_string()),
+            },
+            (":save", Some(path)) => {
+                self.save(path)?;
+                format!("saved {} statements to {}", self.history.len(), path)
+            }
+            (":load", Some(path)) => {
+                let statements = self.load(path)?;
+                format!("replayed {} statements from {}, every result matched", statements, path)
+            }
+            (":ops", _) => operation_table(self.num_type),
+            (":vars", _) => self
+                .vars
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            (":clear", _) => {
+                self.vars.clear();
+                "variables cleared".to_string()
+            }
+            _ => return Err(format!("unknown command `{}`, try :help", line)),
+        };
+        Ok(Some(output))
+    }
+}
+
+// The registered operations of a type, one aligned line each
+pub fn operation_table(num_type: NumType) -> String {
+    let rows = num_type.operations();
+    let width = |column: usize| rows.iter().map(|row| row[column].len()).max().unwrap_or(0);
+    let (name, symbol, arity) = (width(0), width(1), width(2));
+    rows.iter()
+        .map(|row| format!("{:<name$}  {:<symbol$}  {:>arity$}  {}", row[0], row[1], row[2], row[3]))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn run(session: &mut Session) -> io::Result<()> {
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut stdout = io::stdout();
+
+    if interactive {
+        println!("calculator REPL, :help for commands");
+    }
+
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            write!(stdout, "[{} {}]> ", session.mode(), session.policy)?;
+            stdout.flush()?;
+        }
+
+        let Some(line) = lines.next() else { break };
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with(':') {
+            match session.command(line) {
+                Ok(Some(output)) if output.is_empty() => {}
+                Ok(Some(output)) => println!("{}", output),
+                Ok(None) => break,
+                Err(err) => eprintln!("error: {}", err),
+            }
+            continue;
+        }
+
+        match session.run_statement(line) {
+            Ok(evaluation) => println!("{}", session.render(&evaluation)),
+            Err(err) => eprintln!("error: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(statements: &[&str]) -> Session {
+        let mut session = Session::new(NumType::I64, OverflowPolicy::Checked);
+        for statement in statements {
+            session.run_statement(statement).unwrap();
+        }
+        session
+    }
+
+    fn replayed(script: &str) -> Result<Session, String> {
+        let mut session = Session::new(NumType::I64, OverflowPolicy::Checked);
+        session.replay(script).map(|()| session)
+    }
+
+    #[test]
+    fn replays_a_saved_script() {
+        let mut original = session(&["x = 40", "x + 2"]);
+        original.command(":type u8").unwrap();
+        original.command(":policy wrapping").unwrap();
+        original.run_statement("200 + 100").unwrap();
+        original.command(":fixed 2 up").unwrap();
+        original.run_statement("2 / 3").unwrap();
+
+        let script = history::to_script(&original.history);
+        let copy = replayed(&script).unwrap();
+        assert_eq!(copy.vars, original.vars);
+        assert_eq!(copy.history.len(), 4);
+        assert_eq!(history::to_script(&copy.history), script);
+        assert_eq!(copy.history[2].result(), "44 overflowed");
+        assert_eq!(copy.mode(), "fixed(2, up)");
+    }
+
+    #[test]
+    fn rejects_a_tampered_result() {
+        let script = history::to_script(&session(&["x = 40", "x + 2"]).history).replace("#= 42", "#= 43");
+        let err = replayed(&script).err().unwrap();
+        assert_eq!(err, "line 7: `x + 2` recorded `43` but replays to `42`");
+
+        let err = replayed("# calculator session\n#= 1\n").err().unwrap();
+        assert_eq!(err, "line 2: result without a statement");
+    }
+
+    #[test]
+    fn refuses_save_and_load_in_a_script() {
+        for command in [":save out.calc", ":load in.calc", ":clear"] {
+            let script = format!("1 + 1\n{}\n", command);
+            let name = command.split_whitespace().next().unwrap();
+            let expected = format!("line 2: `{}` is not allowed in a session script", name);
+            assert_eq!(replayed(&script).err(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn undo_restores_the_variables() {
+        let mut session = session(&["x = 1", "x = x + 1"]);
+        assert_eq!(session.vars["x"], "2");
+
+        assert_eq!(session.undo().map(|entry| entry.input.clone()), Some("x = x + 1".to_string()));
+        assert_eq!(session.vars["x"], "1");
+        assert_eq!(session.vars["_"], "1");
+
+        session.undo();
+        assert!(session.vars.is_empty());
+        assert!(session.undo().is_none());
+    }
+
+    #[test]
+    fn redo_runs_under_the_original_settings() {
+        let mut session = Session::new(NumType::U8, OverflowPolicy::Wrapping);
+        session.run_statement("200 + 100").unwrap();
+        session.undo();
+        session.command(":type i64").unwrap();
+        session.command(":policy checked").unwrap();
+
+        let evaluation = session.redo().unwrap().unwrap();
+        assert_eq!(evaluation.value, "44");
+        assert!(evaluation.overflowed);
+        let entry = session.history.last().unwrap();
+        assert_eq!((entry.num_type, entry.policy), (NumType::U8, OverflowPolicy::Wrapping));
+        assert_eq!(session.mode(), "i64");
+        assert!(session.redo().is_none());
+    }
+
+    #[test]
+    fn a_new_statement_clears_redo() {
+        let mut session = session(&["1", "2"]);
+        session.undo();
+        session.undo();
+        session.run_statement("3").unwrap();
+        assert!(session.redo().is_none());
+        assert_eq!(session.command(":redo"), Err("nothing to redo".to_string()));
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::arithmetic::{CalcError, OverflowPolicy};
+use crate::calculator::Calculator;
+use crate::eval::{EvalError, Operand};
+use crate::registry::{apply, Registry};
+
+/** REPORT
+ * Every registered operation on a pair of operands, as data instead of the Display text block
+ *  - operations that honour the overflow policy get one row per policy
+ *  - bit rows only exist for integer types, shift and rotate amounts are m2
+ *
+ * Each value is given in decimal, hex and binary (two's complement for integers, IEEE-754 bits for floats)
+ * and the report renders as an aligned text table, JSON, CSV or a Markdown table
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Text => "text",
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Markdown => "markdown",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "markdown" | "md" => Ok(Format::Markdown),
+            other => Err(format!("unknown format `{}`", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Value {
+    pub decimal: String,
+    pub hex: String,
+    pub binary: String,
+}
+
+impl Value {
+    pub fn of<T: Operand>(value: T) -> Self {
+        Self {
+            decimal: value.to_string(),
+            hex: value.to_hex(),
+            binary: value.to_binary(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub operation: &'static str,
+    // None for bit operations, which have no overflow policy
+    pub policy: Option<OverflowPolicy>,
+    pub result: Result<Value, String>,
+    pub overflowed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub type_name: &'static str,
+    pub m1: Value,
+    pub m2: Value,
+    pub rows: Vec<Row>,
+}
+
+pub fn build_report<T: Operand>(m1: &str, m2: &str) -> Result<Report, EvalError> {
+    let parse = |literal: &str| {
+        T::parse_literal(literal).ok_or_else(|| EvalError::InvalidLiteral {
+            literal: literal.to_string(),
+            type_name: T::NAME,
+        })
+    };
+    let calc = Calculator { m1: parse(m1)?, m2: parse(m2)? };
+    let registry = Registry::standard();
+
+    //E floats answer None to every bit operation, so they simply get no bit rows
+    let mut rows = Vec::new();
+    for operation in registry.iter() {
+        let policies: Vec<Option<OverflowPolicy>> = if operation.uses_policy() {
+            OverflowPolicy::ALL.into_iter().map(Some).collect()
+        } else {
+            vec![None]
+        };
+        for policy in policies {
+            let Some(result) = apply(operation, &calc, policy.unwrap_or(OverflowPolicy::Checked)) else {
+                continue;
+            };
+            rows.push(Row {
+                operation: operation.label(),
+                policy,
+                //E a checked overflow is still an overflow, only reported as an error instead of a value
+                overflowed: matches!(result, Ok((_, true)) | Err(CalcError::Overflow { .. })),
+                result: result.map(|(value, _)| Value::of(value)).map_err(|err| err.to_string()),
+            });
+        }
+    }
+
+    Ok(Report {
+        type_name: T::NAME,
+        m1: Value::of(calc.m1),
+        m2: Value::of(calc.m2),
+        rows,
+    })
+}
+
+impl Report {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.to_text(),
+            Format::Json => self.to_json(),
+            Format::Csv => self.to_csv(),
+            Format::Markdown => self.to_markdown(),
+        }
+    }
+
+    // operation, policy, decimal, hex, binary, overflowed, error
+    fn cells(&self) -> Vec<[String; 7]> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let policy = row.policy.map(|policy| policy.to_string()).unwrap_or_default();
+                let overflowed = row.overflowed.to_string();
+                match &row.result {
+                    Ok(value) => [
+                        row.operation.to_string(),
+                        policy,
+                        value.decimal.clone(),
+                        value.hex.clone(),
+                        value.binary.clone(),
+                        overflowed,
+                        String::new(),
+                    ],
+                    Err(err) => [
+                        row.operation.to_string(),
+                        policy,
+                        String::new(),
+                        String::new(),
+                        String::new(),
+                        overflowed,
+                        err.clone(),
+                    ],
+                }
+            })
+            .collect()
+    }
+
+    fn title(&self) -> String {
+        format!("{}: m1 = {}, m2 = {}", self.type_name, self.m1.decimal, self.m2.decimal)
+    }
+
+    fn to_text(&self) -> String {
+        let cells = self.cells();
+        let mut widths = HEADER.map(str::len);
+        for row in &cells {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let line = |row: [&str; 7]| {
+            let padded: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+
+        let mut out = self.title();
+        out += &format!("\n{}", line(HEADER));
+        for row in &cells {
+            out += &format!("\n{}", line(row.each_ref().map(String::as_str)));
+        }
+        out
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = HEADER.join(",");
+        for row in self.cells() {
+            let fields: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+            out += &format!("\n{}", fields.join(","));
+        }
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("### {}\n\n", self.title());
+        out += &format!("| {} |\n", HEADER.join(" | "));
+        out += &format!("|{}\n", "---|".repeat(HEADER.len()));
+        for row in self.cells() {
+            let cells: Vec<String> = row.iter().map(|cell| markdown_cell(cell)).collect();
+            out += &format!("| {} |\n", cells.join(" | "));
+        }
+        out.trim_end().to_string()
+    }
+
+    //E values are JSON strings: u128 / U256 don't fit in a JSON number without losing precision
+    fn to_json(&self) -> String {
+        let value = |value: &Value| {
+            format!(
+                "{{\"decimal\": {}, \"hex\": {}, \"binary\": {}}}",
+                json_string(&value.decimal),
+                json_string(&value.hex),
+                json_string(&value.binary)
+            )
+        };
+
+        let rows: Vec<String> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let policy = row.policy.map_or("null".to_string(), |policy| json_string(&policy.to_string()));
+                let result = match &row.result {
+                    Ok(result) => format!("\"value\": {}", value(result)),
+                    Err(err) => format!("\"error\": {}", json_string(err)),
+                };
+                format!(
+                    "    {{\"operation\": {}, \"policy\": {}, {}, \"overflowed\": {}}}",
+                    json_string(row.operation),
+                    policy,
+                    result,
+                    row.overflowed
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\n  \"type\": {},\n  \"m1\": {},\n  \"m2\": {},\n  \"rows\": [\n{}\n  ]\n}}",
+            json_string(self.type_name),
+            value(&self.m1),
+            value(&self.m2),
+            rows.join(",\n")
+        )
+    }
+}
+
+const HEADER: [&str; 7] = ["operation", "policy", "decimal", "hex", "binary", "overflowed", "error"];
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if c.is_control() => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// RFC 4180: quote fields containing a separator, a quote or a line break
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_checked_overflows() {
+        let report = build_report::<i8>("127", "1").unwrap();
+        let addition: Vec<&Row> = report.rows.iter().filter(|row| row.operation == "Addition").collect();
+        assert_eq!(addition.len(), OverflowPolicy::ALL.len());
+        assert!(addition.iter().all(|row| row.overflowed));
+
+        let checked = addition.iter().find(|row| row.policy == Some(OverflowPolicy::Checked)).unwrap();
+        assert!(checked.result.is_err());
+        assert_eq!(report.render(Format::Csv).lines().nth(1), Some("Addition,checked,,,,true,Addition overflow"));
+    }
+
+    #[test]
+    fn leaves_other_errors_unflagged() {
+        let report = build_report::<i8>("1", "0").unwrap();
+        let division_by_zero: Vec<&Row> = report
+            .rows
+            .iter()
+            .filter(|row| row.result.as_ref().is_err_and(|err| *err == CalcError::DivisionByZero.to_string()))
+            .collect();
+        assert!(!division_by_zero.is_empty());
+        assert!(division_by_zero.iter().all(|row| !row.overflowed));
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a \"quote\""), "\"a \\\"quote\\\"\"");
+        assert_eq!(json_string("back\\slash"), "\"back\\\\slash\"");
+        assert_eq!(json_string("new\nline"), "\"new\\nline\"");
+        assert_eq!(json_string("tab\t"), "\"tab\\u0009\"");
+        assert_eq!(json_string("ü"), "\"ü\"");
+    }
+
+    #[test]
+    fn escapes_markdown_pipes() {
+        assert_eq!(markdown_cell("a | b"), "a \\| b");
+        assert_eq!(markdown_cell("||"), "\\|\\|");
+        assert_eq!(markdown_cell("plain"), "plain");
+    }
+}
@@ -0,0 +1,626 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+use std::str::FromStr;
+
+use crate::arithmetic::{overflowing_pow_by_squaring, CalcError, ExtendedArithmetic, OverflowArithmetic};
+use crate::bits::Bits;
+
+/** U256
+ * 256-bit unsigned integer stored as two 128-bit limbs, like Cairo's `u256 { low, high }`
+ * Covers Solidity's `uint256` and the u128 * u128 intermediates of Solana programs
+ *  - every Calculator operation through OverflowArithmetic, ExtendedArithmetic and Bits
+ *  - `full_mul`: the exact 512-bit product as (low, high) halves
+ *  - `mul_div`: a * b / d on the 512-bit product, like Solidity's FullMath.mulDiv
+ *  - decimal and hex parsing / formatting
+ */
+// `high` is declared first so the derived ordering compares the most significant limb first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct U256 {
+    high: u128,
+    low: u128,
+}
+
+impl U256 {
+    pub const ZERO: U256 = U256 { high: 0, low: 0 };
+    pub const ONE: U256 = U256 { high: 0, low: 1 };
+    pub const MAX: U256 = U256 { high: u128::MAX, low: u128::MAX };
+
+    // Least significant 128 bits, `amount.low` in Cairo
+    pub fn low(self) -> u128 {
+        self.low
+    }
+
+    // Most significant 128 bits, `amount.high` in Cairo
+    pub fn high(self) -> u128 {
+        self.high
+    }
+
+    // Little-endian bytes, `low` first, like the primitive `to_le_bytes`
+    pub fn to_le_bytes(self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        bytes[..16].copy_from_slice(&self.low.to_le_bytes());
+        bytes[16..].copy_from_slice(&self.high.to_le_bytes());
+        bytes
+    }
+
+    // Little-endian 64-bit words
+    fn words(self) -> [u64; 4] {
+        [self.low as u64, (self.low >> 64) as u64, self.high as u64, (self.high >> 64) as u64]
+    }
+
+    fn from_words(words: [u64; 4]) -> Self {
+        U256 {
+            low: words[0] as u128 | (words[1] as u128) << 64,
+            high: words[2] as u128 | (words[3] as u128) << 64,
+        }
+    }
+
+    fn bit(self, index: u32) -> bool {
+        if index < 128 {
+            self.low >> index & 1 == 1
+        } else {
+            self.high >> (index - 128) & 1 == 1
+        }
+    }
+
+    // Exact product as (low, high) 256-bit halves
+    pub fn full_mul(self, rhs: U256) -> (U256, U256) {
+        let (a, b) = (self.words(), rhs.words());
+        let mut product = [0u64; 8];
+
+        //E schoolbook multiplication on 64-bit words, every partial product fits in a u128
+        for i in 0..4 {
+            let mut carry: u128 = 0;
+            for j in 0..4 {
+                let current = product[i + j] as u128 + a[i] as u128 * b[j] as u128 + carry;
+                product[i + j] = current as u64;
+                carry = current >> 64;
+            }
+            product[i + 4] = carry as u64;
+        }
+
+        (
+            U256::from_words([product[0], product[1], product[2], product[3]]),
+            U256::from_words([product[4], product[5], product[6], product[7]]),
+        )
+    }
+
+    // (high * 2^256 + low) / divisor, None when the divisor is zero or the quotient needs more than 256 bits
+    fn div_rem_wide(high: U256, low: U256, divisor: U256) -> Option<(U256, U256)> {
+        if divisor == U256::ZERO || high >= divisor {
+            return None;
+        }
+
+        //E restoring long division, one bit of `low` at a time; the remainder always stays below divisor
+        let mut remainder = high;
+        let mut quotient = U256::ZERO;
+        for index in (0..256).rev() {
+            let carry = remainder.bit(255);
+            remainder = remainder.shl(1);
+            if low.bit(index) {
+                remainder.low |= 1;
+            }
+            if carry || remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient = quotient | U256::ONE.shl(index);
+            }
+        }
+        Some((quotient, remainder))
+    }
+
+    pub fn div_rem(self, divisor: U256) -> Option<(U256, U256)> {
+        U256::div_rem_wide(U256::ZERO, self, divisor)
+    }
+
+    // a * b / d with a 512-bit intermediate, rounded down
+    pub fn mul_div(self, rhs: U256, denominator: U256) -> Result<U256, CalcError> {
+        if denominator == U256::ZERO {
+            return Err(CalcError::DivisionByZero);
+        }
+        let (low, high) = self.full_mul(rhs);
+        U256::div_rem_wide(high, low, denominator)
+            .map(|(quotient, _)| quotient)
+            .ok_or(CalcError::Overflow { operation: "Mul-div" })
+    }
+
+    // the low 256 bits of a * b / d, and whether the full quotient needed more
+    pub fn overflowing_mul_div(self, rhs: U256, denominator: U256) -> Result<(U256, bool), CalcError> {
+        let (low, high) = self.full_mul(rhs);
+        //E high / d is the part of the quotient above 2^256, its remainder carries into the division of low
+        let (quotient_high, remainder) = high.div_rem(denominator).ok_or(CalcError::DivisionByZero)?;
+        let (quotient, _) = U256::div_rem_wide(remainder, low, denominator).ok_or(CalcError::DivisionByZero)?;
+        Ok((quotient, quotient_high != U256::ZERO))
+    }
+
+    pub fn from_str_radix(digits: &str, radix: u32) -> Result<U256, String> {
+        if digits.is_empty() {
+            return Err("empty number".to_string());
+        }
+        let radix_value = U256::from(radix as u128);
+        digits.chars().try_fold(U256::ZERO, |acc, c| {
+            let digit = c
+                .to_digit(radix)
+                .ok_or_else(|| format!("invalid digit `{}` in base {}", c, radix))?;
+            acc.checked_mul(radix_value)
+                .and_then(|acc| acc.checked_add(U256::from(digit as u128)))
+                .ok_or_else(|| "number does not fit in 256 bits".to_string())
+        })
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(value: u128) -> Self {
+        U256 { high: 0, low: value }
+    }
+}
+
+// Decimal, or hexadecimal with a `0x` prefix
+impl FromStr for U256 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => U256::from_str_radix(hex, 16),
+            None => U256::from_str_radix(s, 10),
+        }
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.high == 0 {
+            return fmt::Display::fmt(&self.low, f);
+        }
+
+        //E peel off 19 decimal digits at a time, 10^19 is the largest power of ten in a u64
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+        let mut chunks = Vec::new();
+        let mut rest = *self;
+        while rest != U256::ZERO {
+            let (quotient, remainder) = rest.div_rem(U256::from(CHUNK)).expect("CHUNK is not zero");
+            chunks.push(remainder.low);
+            rest = quotient;
+        }
+
+        let mut digits = chunks.pop().map(|chunk| chunk.to_string()).unwrap_or_default();
+        for chunk in chunks.iter().rev() {
+            digits += &format!("{:019}", chunk);
+        }
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+impl fmt::LowerHex for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = if self.high == 0 {
+            format!("{:x}", self.low)
+        } else {
+            format!("{:x}{:032x}", self.high, self.low)
+        };
+        f.pad_integral(true, "0x", &digits)
+    }
+}
+
+impl fmt::Binary for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = if self.high == 0 {
+            format!("{:b}", self.low)
+        } else {
+            format!("{:b}{:0128b}", self.high, self.low)
+        };
+        f.pad_integral(true, "0b", &digits)
+    }
+}
+
+impl BitAnd for U256 {
+    type Output = U256;
+
+    fn bitand(self, rhs: U256) -> U256 {
+        U256 { high: self.high & rhs.high, low: self.low & rhs.low }
+    }
+}
+
+impl BitOr for U256 {
+    type Output = U256;
+
+    fn bitor(self, rhs: U256) -> U256 {
+        U256 { high: self.high | rhs.high, low: self.low | rhs.low }
+    }
+}
+
+impl BitXor for U256 {
+    type Output = U256;
+
+    fn bitxor(self, rhs: U256) -> U256 {
+        U256 { high: self.high ^ rhs.high, low: self.low ^ rhs.low }
+    }
+}
+
+impl Not for U256 {
+    type Output = U256;
+
+    fn not(self) -> U256 {
+        U256 { high: !self.high, low: !self.low }
+    }
+}
+
+impl OverflowArithmetic for U256 {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.overflowing_add(rhs) {
+            (value, false) => Some(value),
+            (_, true) => None,
+        }
+    }
+
+    fn wrapping_add(self, rhs: Self) -> Self {
+        self.overflowing_add(rhs).0
+    }
+
+    fn saturating_add(self, rhs: Self) -> Self {
+        self.checked_add(rhs).unwrap_or(U256::MAX)
+    }
+
+    fn overflowing_add(self, rhs: Self) -> (Self, bool) {
+        let (low, carry) = self.low.overflowing_add(rhs.low);
+        let (high, overflow_high) = self.high.overflowing_add(rhs.high);
+        let (high, overflow_carry) = high.overflowing_add(carry as u128);
+        (U256 { high, low }, overflow_high || overflow_carry)
+    }
+
+    fn checked_sub(self, rhs: Self) -> Option<Self> {
+        match self.overflowing_sub(rhs) {
+            (value, false) => Some(value),
+            (_, true) => None,
+        }
+    }
+
+    fn wrapping_sub(self, rhs: Self) -> Self {
+        self.overflowing_sub(rhs).0
+    }
+
+    fn saturating_sub(self, rhs: Self) -> Self {
+        self.checked_sub(rhs).unwrap_or(U256::ZERO)
+    }
+
+    fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
+        let (low, borrow) = self.low.overflowing_sub(rhs.low);
+        let (high, underflow_high) = self.high.overflowing_sub(rhs.high);
+        let (high, underflow_borrow) = high.overflowing_sub(borrow as u128);
+        (U256 { high, low }, underflow_high || underflow_borrow)
+    }
+
+    fn checked_mul(self, rhs: Self) -> Option<Self> {
+        match self.overflowing_mul(rhs) {
+            (value, false) => Some(value),
+            (_, true) => None,
+        }
+    }
+
+    fn wrapping_mul(self, rhs: Self) -> Self {
+        self.full_mul(rhs).0
+    }
+
+    fn saturating_mul(self, rhs: Self) -> Self {
+        self.checked_mul(rhs).unwrap_or(U256::MAX)
+    }
+
+    fn overflowing_mul(self, rhs: Self) -> (Self, bool) {
+        let (low, high) = self.full_mul(rhs);
+        (low, high != U256::ZERO)
+    }
+
+    //E unsigned division never overflows, only a zero divisor fails
+    fn checked_div(self, rhs: Self) -> Option<Self> {
+        self.div_rem(rhs).map(|(quotient, _)| quotient)
+    }
+
+    fn wrapping_div(self, rhs: Self) -> Self {
+        self.checked_div(rhs).expect("division by zero is rejected by the Calculator")
+    }
+
+    fn saturating_div(self, rhs: Self) -> Self {
+        self.wrapping_div(rhs)
+    }
+
+    fn overflowing_div(self, rhs: Self) -> (Self, bool) {
+        (self.wrapping_div(rhs), false)
+    }
+
+    fn is_zero(self) -> bool {
+        self == U256::ZERO
+    }
+}
+
+impl ExtendedArithmetic for U256 {
+    type Exponent = u32;
+
+    //E unsigned, like u128: -x only fits for 0 and abs is the identity
+    fn checked_neg(self) -> Option<Self> {
+        U256::ZERO.checked_sub(self)
+    }
+
+    fn wrapping_neg(self) -> Self {
+        U256::ZERO.wrapping_sub(self)
+    }
+
+    fn saturating_neg(self) -> Self {
+        U256::ZERO
+    }
+
+    fn overflowing_neg(self) -> (Self, bool) {
+        U256::ZERO.overflowing_sub(self)
+    }
+
+    fn checked_abs(self) -> Option<Self> {
+        Some(self)
+    }
+
+    fn wrapping_abs(self) -> Self {
+        self
+    }
+
+    fn saturating_abs(self) -> Self {
+        self
+    }
+
+    fn overflowing_abs(self) -> (Self, bool) {
+        (self, false)
+    }
+
+    fn exponent(self) -> Option<u32> {
+        self.to_bit_index()
+    }
+
+    fn checked_pow(self, exp: u32) -> Option<Self> {
+        match self.overflowing_pow(exp) {
+            (value, false) => Some(value),
+            (_, true) => None,
+        }
+    }
+
+    fn wrapping_pow(self, exp: u32) -> Self {
+        self.overflowing_pow(exp).0
+    }
+
+    fn saturating_pow(self, exp: u32) -> Self {
+        self.checked_pow(exp).unwrap_or(U256::MAX)
+    }
+
+    fn overflowing_pow(self, exp: u32) -> (Self, bool) {
+        overflowing_pow_by_squaring(self, exp, U256::ONE)
+    }
+
+    // Bit by bit from the top: the root of a 256-bit value fits in 128 bits, so its square never overflows
+    fn sqrt(self) -> Option<Self> {
+        let mut root = 0u128;
+        for bit in (0..128).rev() {
+            let candidate = root | 1 << bit;
+            let (square, high) = U256::from(candidate).full_mul(U256::from(candidate));
+            if high == U256::ZERO && square <= self {
+                root = candidate;
+            }
+        }
+        Some(U256::from(root))
+    }
+
+    fn log2(self) -> Option<Self> {
+        (self != U256::ZERO).then(|| U256::from((255 - Bits::leading_zeros(self)) as u128))
+    }
+
+    fn log10(self) -> Option<Self> {
+        if self == U256::ZERO {
+            return None;
+        }
+        let (ten, mut value, mut log) = (U256::from(10), self, 0u128);
+        while value >= ten {
+            value = value.wrapping_div(ten);
+            log += 1;
+        }
+        Some(U256::from(log))
+    }
+
+    fn from_len(len: usize) -> Option<Self> {
+        Some(U256::from(len as u128))
+    }
+}
+
+impl Bits for U256 {
+    const BITS: u32 = 256;
+    const SIGNED: bool = false;
+    const ZERO: Self = U256::ZERO;
+
+    fn to_bit_index(self) -> Option<u32> {
+        if self.high != 0 {
+            return None;
+        }
+        u32::try_from(self.low).ok()
+    }
+
+    fn from_count(count: u32) -> Self {
+        U256::from(count as u128)
+    }
+
+    fn shl(self, amount: u32) -> Self {
+        match amount {
+            0 => self,
+            1..=127 => U256 {
+                high: self.high << amount | self.low >> (128 - amount),
+                low: self.low << amount,
+            },
+            _ => U256 { high: self.low << (amount - 128), low: 0 },
+        }
+    }
+
+    fn logical_shr(self, amount: u32) -> Self {
+        match amount {
+            0 => self,
+            1..=127 => U256 {
+                high: self.high >> amount,
+                low: self.low >> amount | self.high << (128 - amount),
+            },
+            _ => U256 { high: 0, low: self.high >> (amount - 128) },
+        }
+    }
+
+    fn arithmetic_shr(self, amount: u32) -> Self {
+        let shifted = self.logical_shr(amount);
+        if self.bit(255) {
+            shifted | !U256::MAX.logical_shr(amount)
+        } else {
+            shifted
+        }
+    }
+
+    fn rotate_left(self, amount: u32) -> Self {
+        if amount == 0 {
+            return self;
+        }
+        self.shl(amount) | self.logical_shr(256 - amount)
+    }
+
+    fn rotate_right(self, amount: u32) -> Self {
+        if amount == 0 {
+            return self;
+        }
+        self.logical_shr(amount) | self.shl(256 - amount)
+    }
+
+    fn count_ones(self) -> u32 {
+        self.high.count_ones() + self.low.count_ones()
+    }
+
+    fn leading_zeros(self) -> u32 {
+        if self.high != 0 {
+            self.high.leading_zeros()
+        } else {
+            128 + self.low.leading_zeros()
+        }
+    }
+
+    fn trailing_zeros(self) -> u32 {
+        if self.low != 0 {
+            self.low.trailing_zeros()
+        } else {
+            128 + self.high.trailing_zeros()
+        }
+    }
+
+    fn low_mask(len: u32) -> Self {
+        if len >= 256 {
+            U256::MAX
+        } else {
+            U256::ONE.shl(len).wrapping_sub(U256::ONE)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_POW_256: &str = "115792089237316195423570985008687907853269984665640564039457584007913129639936";
+    const MAX_DECIMAL: &str = "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+    fn pow2(exponent: u32) -> U256 {
+        U256::ONE.shl(exponent)
+    }
+
+    #[test]
+    fn multiplies_max_by_max() {
+        //E (2^256 - 1)^2 = (2^256 - 2) * 2^256 + 1
+        assert_eq!(U256::MAX.full_mul(U256::MAX), (U256::ONE, U256::MAX.wrapping_sub(U256::ONE)));
+        assert_eq!(U256::MAX.checked_mul(U256::MAX), None);
+        assert_eq!(U256::MAX.wrapping_mul(U256::MAX), U256::ONE);
+        assert_eq!(U256::MAX.saturating_mul(U256::MAX), U256::MAX);
+        assert_eq!(U256::MAX.overflowing_mul(U256::MAX), (U256::ONE, true));
+        assert_eq!(U256::MAX.full_mul(U256::ONE), (U256::MAX, U256::ZERO));
+    }
+
+    #[test]
+    fn mul_divs_through_the_wide_product() {
+        //E 2^255 * 4 needs 258 bits before the division brings it back
+        assert_eq!(pow2(255).mul_div(U256::from(4), U256::from(8)), Ok(pow2(254)));
+        assert_eq!(U256::MAX.mul_div(U256::MAX, U256::MAX), Ok(U256::MAX));
+        assert_eq!(U256::MAX.mul_div(U256::from(2), U256::ONE), Err(CalcError::Overflow { operation: "Mul-div" }));
+        assert_eq!(U256::ONE.mul_div(U256::ONE, U256::ZERO), Err(CalcError::DivisionByZero));
+        assert_eq!(U256::from(7).mul_div(U256::from(3), U256::from(2)), Ok(U256::from(10)));
+    }
+
+    #[test]
+    fn overflowing_mul_divs_keep_the_low_bits() {
+        //E 2^255 * 6 / 2 is 3 * 2^255, which keeps 2^255 after dropping bit 256
+        assert_eq!(pow2(255).overflowing_mul_div(U256::from(6), U256::from(2)), Ok((pow2(255), true)));
+        assert_eq!(U256::MAX.overflowing_mul_div(U256::MAX, U256::MAX), Ok((U256::MAX, false)));
+        assert_eq!(U256::MAX.overflowing_mul_div(U256::from(2), U256::ONE), Ok((U256::MAX.shl(1), true)));
+        assert_eq!(U256::ONE.overflowing_mul_div(U256::ONE, U256::ZERO), Err(CalcError::DivisionByZero));
+    }
+
+    #[test]
+    fn divides_wide_values() {
+        assert_eq!(U256::div_rem_wide(U256::ONE, U256::ZERO, U256::from(2)), Some((pow2(255), U256::ZERO)));
+        let quotient = pow2(255).wrapping_add(U256::from(2));
+        assert_eq!(U256::div_rem_wide(U256::ONE, U256::from(5), U256::from(2)), Some((quotient, U256::ONE)));
+        //E the quotient of high * 2^256 + low needs more than 256 bits once high reaches the divisor
+        assert_eq!(U256::div_rem_wide(U256::from(2), U256::ZERO, U256::from(2)), None);
+        assert_eq!(U256::div_rem_wide(U256::ZERO, U256::ONE, U256::ZERO), None);
+        assert_eq!(U256::MAX.div_rem(U256::from(10)), Some((MAX_DECIMAL[..77].parse().unwrap(), U256::from(5))));
+    }
+
+    #[test]
+    fn shifts_across_the_limbs() {
+        assert_eq!(pow2(127), U256 { high: 0, low: 1 << 127 });
+        assert_eq!(pow2(128), U256 { high: 1, low: 0 });
+        assert_eq!(pow2(129), U256 { high: 2, low: 0 });
+        assert_eq!(pow2(255), U256 { high: 1 << 127, low: 0 });
+
+        assert_eq!(U256::MAX.logical_shr(127), U256 { high: 1, low: u128::MAX });
+        assert_eq!(U256::MAX.logical_shr(128), U256 { high: 0, low: u128::MAX });
+        assert_eq!(U256::MAX.logical_shr(129), U256 { high: 0, low: u128::MAX >> 1 });
+        assert_eq!(U256::MAX.logical_shr(255), U256::ONE);
+
+        assert_eq!(pow2(255).arithmetic_shr(128), U256 { high: u128::MAX, low: 1 << 127 });
+        assert_eq!(pow2(254).arithmetic_shr(129), pow2(125));
+        assert_eq!(pow2(255).rotate_left(1), U256::ONE);
+    }
+
+    #[test]
+    fn parses_and_formats_decimal() {
+        assert_eq!(MAX_DECIMAL.parse::<U256>(), Ok(U256::MAX));
+        assert_eq!(U256::MAX.to_string(), MAX_DECIMAL);
+        assert_eq!(pow2(128).to_string(), "340282366920938463463374607431768211456");
+        //E a 19 digit chunk with leading zeros keeps them
+        let chunked = "10000000000000000000000000000000000000001";
+        assert_eq!(chunked.parse::<U256>().unwrap().to_string(), chunked);
+        assert_eq!(U256::ZERO.to_string(), "0");
+        assert_eq!(format!("{:>6}", U256::from(42)), "    42");
+        assert_eq!(format!("{:>41}", pow2(128)), format!("  {}", pow2(128)));
+    }
+
+    #[test]
+    fn parses_and_formats_hex() {
+        let max_hex = format!("0x{}", "f".repeat(64));
+        assert_eq!(max_hex.parse::<U256>(), Ok(U256::MAX));
+        assert_eq!(format!("{:#x}", U256::MAX), max_hex);
+        assert_eq!(format!("{:x}", pow2(128)), format!("1{}", "0".repeat(32)));
+        assert_eq!(format!("{:x}", U256::from(255)), "ff");
+        assert_eq!("0XfF".parse::<U256>(), Ok(U256::from(255)));
+        assert_eq!(format!("{:b}", pow2(128)), format!("1{}", "0".repeat(128)));
+    }
+
+    #[test]
+    fn parses_any_radix() {
+        assert_eq!(U256::from_str_radix("ff", 16), Ok(U256::from(255)));
+        assert_eq!(U256::from_str_radix("1010", 2), Ok(U256::from(10)));
+        assert_eq!(U256::from_str_radix(&"1".repeat(256), 2), Ok(U256::MAX));
+        assert_eq!(U256::from_str_radix("", 10), Err("empty number".to_string()));
+        assert_eq!(U256::from_str_radix("12", 2), Err("invalid digit `2` in base 2".to_string()));
+    }
+
+    #[test]
+    fn rejects_two_pow_256() {
+        let too_big = Err("number does not fit in 256 bits".to_string());
+        assert_eq!(TWO_POW_256.parse::<U256>(), too_big);
+        assert_eq!(format!("0x1{}", "0".repeat(64)).parse::<U256>(), too_big);
+        assert_eq!(U256::from_str_radix(&format!("1{}", "0".repeat(256)), 2), too_big);
+    }
+}
@@ -1,16 +1,45 @@
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::U64;
 use near_sdk::serde::Serialize;
-use near_sdk::store::Vector;
-use near_sdk::{env, near_bindgen, AccountId, NearToken};
+use near_sdk::store::{LookupMap, LookupSet, Vector};
+use near_sdk::{env, near_bindgen, require, AccountId, Gas, NearToken, Promise, PromiseError};
 
 /** SUMMARY OF THE CODE
  * Allow anyone to create an instance of a contract allowing external users to send messages to the contract
- * A message is premium if the attached deposit is greater than or equal to POINT_ONE
- * the contract store all the messages in a vector
- * the contract provide 3 functions:
+ * A message is premium if the attached deposit is greater than or equal to the premium threshold (POINT_ONE by default)
+ * The owner set at init can change the premium threshold and the maximum page size without redeploying
+ * The contract counts the deposits it keeps, only the owner can withdraw them
+ * The sender pays for the storage a message uses out of the attached deposit, what is left decides if the message is premium
+ * What is left of a plain message's deposit is sent back, and when refund_excess is on the part of a premium deposit above the premium threshold too
+ * A message text can't be blank, longer than max_message_length bytes or hold control characters other than line breaks
+ * Each message records its id, the block timestamp and height it was posted at, the deposit attached to it and the part of it the contract kept
+ * the contract store all the messages in a vector, and the indexes of each sender's messages in a lookup map
+ * each stored message starts with its layout version, so a new message field doesn't break the messages already stored
+ * a page of messages never holds more than max_page_size messages, so a view call costs the same wherever the page starts
+ * A sender can edit or delete their messages: edits keep the previous texts, a deleted message stays in the vector as a tombstone
+ * Premium messages are also indexed in a premium feed, so they can be paged without reading the plain ones
+ * When the owner sets min_post_interval, an account must wait that long between two plain messages, premium messages are exempt
+ * The owner appoints moderators, the owner and the moderators can hide a message and show it again
+ * Anyone can flag a message once, hidden messages stay in the vector and can be left out of get_messages
+ * every view returns a hidden message with an empty text and without its previous texts, only its metadata stays public
+ * Adding, editing and deleting a message logs a NEP-297 event (EVENT_JSON:...), so an indexer doesn't have to poll get_messages
+ * the contract provide the following functions:
+ * - new: allow to initialize the contract with its owner, migrate: allow to upgrade the state of the first version of the contract
+ * - migrate_batch: allow the owner to migrate the messages of the first version a batch at a time
+ * - set_premium_threshold / set_max_page_size / set_max_message_length / set_refund_excess / set_min_post_interval: allow the owner to change the settings, get_config: allow to get them
+ * - add_moderator / remove_moderator: allow the owner to manage the moderators, get_role: allow to get the role of an account
+ * - hide_message / unhide_message: allow a moderator to hide a message or show it again
+ * - flag_message: allow anyone to report a message to the moderators
+ * - withdraw: allow the owner to withdraw the kept deposits, get_withdrawable_deposits: allow to get their amount
  * - add_message: allow to add a message to the contract
- * - get_messages: allow to get the messages from the contract
+ * - edit_message / delete_message: allow the sender of a message to change it or delete it
+ * - get_message_revisions: allow to get the previous texts of an edited message
+ * - get_messages: allow to get a page of messages from the contract, oldest or newest first, with or without the hidden ones
+ * - get_message: allow to get a message by id
+ * - get_messages_in_time_range: allow to get a page of the messages posted between two timestamps
+ * - get_messages_by_sender: allow to get a page of the messages posted by an account
+ * - total_messages_by_sender: allow to get the number of messages posted by an account
+ * - get_premium_messages / total_premium_messages: allow to get a page of the premium messages and their number
  * - total_messages: allow to get the total number of messages in the contract
  */
 
@@ -18,31 +47,261 @@ use near_sdk::{env, near_bindgen, AccountId, NearToken};
 //E set one to 100 
 const POINT_ONE: NearToken = NearToken::from_millinear(100);
 
+//E premium threshold of a new contract, the owner can change it with set_premium_threshold
+const DEFAULT_PREMIUM_THRESHOLD: NearToken = POINT_ONE;
+
+//E maximum number of messages a page can hold unless the owner configures it otherwise
+const DEFAULT_MAX_PAGE_SIZE: u32 = 50;
+
+//E maximum length in bytes of a message text unless the owner configures it otherwise
+const DEFAULT_MAX_MESSAGE_LENGTH: u32 = 1000;
+
+//E nanoseconds an account waits between two plain messages unless the owner configures it otherwise, 0 turns the limit off
+const DEFAULT_MIN_POST_INTERVAL: u64 = 0;
+
+//E NEP-297 standard name and version of the events the contract logs
+const EVENT_STANDARD: &str = "guestbook";
+const EVENT_VERSION: &str = "1.0.0";
+
+//E gas reserved for on_withdraw, which only updates a counter
+const ON_WITHDRAW_GAS: Gas = Gas::from_tgas(5);
+
 //E @question what is serde ?
-#[derive(BorshDeserialize, BorshSerialize, Serialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 #[borsh(crate = "near_sdk::borsh")]
-//E create PostedMessage struct
+//E create PostedMessage struct, the current layout of a message
 pub struct PostedMessage {
+    //E the index of the message in the messages vector, messages are never removed so it never changes
+    pub id: U64,
+    pub premium: bool,
+    pub sender: AccountId,
+    pub text: String,
+    //E env::block_timestamp() in nanoseconds
+    pub timestamp: U64,
+    pub block_height: U64,
+    //E env::attached_deposit() of the add_message call
+    pub deposit: NearToken,
+    //E the part of deposit the contract kept, without the storage cost and the refunded part
+    pub kept: NearToken,
+    //E timestamp of the last edit, None if the message was never edited
+    pub edited_at: Option<U64>,
+    //E a deleted message keeps its place (and id) with an empty text
+    pub deleted: bool,
+    //E hidden by a moderator, the text is kept so the moderator can show it again but the views return it empty
+    pub hidden: bool,
+    //E number of accounts that flagged the message
+    pub flags: u32,
+}
+
+//E layout of a message in the first version of the contract
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct PostedMessageV1 {
     pub premium: bool,
     pub sender: AccountId,
     pub text: String,
 }
 
+//E a message as stored in the messages vector, the borsh tag in front tells which layout follows
+//E a field added to a stored struct breaks the deserialization of every message stored before, add a variant instead
+//E a new layout goes in a new variant at the end, older variants are upgraded when they are read or changed
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum VersionedPostedMessage {
+    V1(PostedMessageV1),
+    V2(PostedMessage),
+}
+
+impl VersionedPostedMessage {
+    //E the message in the current layout, the fields its version didn't record are empty
+    fn latest(&self, index: u32) -> PostedMessage {
+        match self {
+            Self::V1(message) => PostedMessage {
+                id: U64(index as u64),
+                premium: message.premium,
+                sender: message.sender.clone(),
+                text: message.text.clone(),
+                timestamp: U64(0),
+                block_height: U64(0),
+                deposit: NearToken::from_yoctonear(0),
+                kept: NearToken::from_yoctonear(0),
+                edited_at: None,
+                deleted: false,
+                hidden: false,
+                flags: 0,
+            },
+            Self::V2(message) => message.clone(),
+        }
+    }
+
+    //E the message as the views return it, a hidden message without its text
+    //E @audit the text of a hidden message is still in the contract state, which anyone can read with a state query
+    fn public(&self, index: u32) -> PostedMessage {
+        let mut message = self.latest(index);
+        if message.hidden {
+            message.text = String::new();
+        }
+        message
+    }
+
+    //E the message in the current layout to change it, an older version is upgraded in place first
+    fn latest_mut(&mut self, index: u32) -> &mut PostedMessage {
+        if let Self::V1(_) = self {
+            *self = Self::V2(self.latest(index));
+        }
+        match self {
+            Self::V2(message) => message,
+            Self::V1(_) => unreachable!("message was upgraded above"),
+        }
+    }
+
+    //E env::block_timestamp() when the message was posted, 0 for the first version which didn't record it
+    fn timestamp(&self) -> u64 {
+        match self {
+            Self::V1(_) => 0,
+            Self::V2(message) => message.timestamp.0,
+        }
+    }
+}
+
+//E a previous text of an edited message, with the timestamp it was written at
+#[derive(BorshDeserialize, BorshSerialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Revision {
+    pub text: String,
+    pub timestamp: U64,
+}
+
+//E NEP-297 event: logged as EVENT_JSON:{"standard":"guestbook","version":"1.0.0","event":"add_message","data":[...]}
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum GuestbookEvent {
+    AddMessage(Vec<MessageEvent>),
+    EditMessage(Vec<MessageEvent>),
+    DeleteMessage(Vec<MessageEvent>),
+    HideMessage(Vec<MessageEvent>),
+    UnhideMessage(Vec<MessageEvent>),
+}
+
+//E the message an event is about, index is its id
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MessageEvent {
+    pub index: U64,
+    pub sender: AccountId,
+    pub premium: bool,
+}
+
+impl MessageEvent {
+    fn of(message: &PostedMessage) -> Self {
+        Self { index: message.id, sender: message.sender.clone(), premium: message.premium }
+    }
+}
+
+//E the event with the standard and version in front
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a GuestbookEvent,
+}
+
+impl GuestbookEvent {
+    fn emit(&self) {
+        let log = EventLog { standard: EVENT_STANDARD, version: EVENT_VERSION, event: self };
+        let json = near_sdk::serde_json::to_string(&log).expect("events serialize to json");
+        env::log_str(&format!("EVENT_JSON:{}", json));
+    }
+}
+
+//E role of an account returned by get_role, the owner can do everything a moderator can
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Owner,
+    Moderator,
+}
+
+//E a page of messages returned by get_messages
+//E next_index is the from_index to pass to get the following page, None on the last page
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MessagePage {
+    pub messages: Vec<PostedMessage>,
+    pub total: U64,
+    pub next_index: Option<U64>,
+    pub has_more: bool,
+}
+
+//E the settings returned by get_config
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Config {
+    pub owner: AccountId,
+    pub premium_threshold: NearToken,
+    pub max_page_size: u32,
+    pub max_message_length: u32,
+    pub refund_excess: bool,
+    pub min_post_interval: U64,
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 #[borsh(crate = "near_sdk::borsh")]
 //E create struct contract
 pub struct Contract {
-    messages: Vector<PostedMessage>,
+    messages: Vector<VersionedPostedMessage>,
+    //E indexes in messages of the messages each account posted, oldest first
+    messages_by_sender: LookupMap<AccountId, Vector<u32>>,
+    //E previous texts of each edited message by id, oldest first, kept apart so reading a message doesn't load its history
+    revisions: LookupMap<u64, Vec<Revision>>,
+    max_page_size: u32,
+    //E the only account allowed to change the settings, appoint moderators and withdraw the deposits
+    owner: AccountId,
+    premium_threshold: NearToken,
+    //E in bytes, the text of a message is what the sender controls the size of
+    max_message_length: u32,
+    //E send back the part of a premium deposit above premium_threshold
+    refund_excess: bool,
+    //E deposits kept by add_message and not withdrawn yet
+    deposits: NearToken,
+    //E indexes in messages of the premium messages, oldest first
+    premium_messages: Vector<u32>,
+    //E nanoseconds between two plain messages of the same account
+    min_post_interval: u64,
+    //E accounts the owner allowed to hide messages
+    moderators: LookupSet<AccountId>,
+    //E (message index, account) of every flag, so an account flags a message once
+    flags: LookupSet<(u32, AccountId)>,
+    //E the messages of the first version, under the same prefix as messages: the ones from messages.len() on aren't migrated yet
+    //E the two vectors share their storage keys, so while it is Some nothing but migrate_batch may push to messages,
+    //E and the views read the indexes from messages.len() on from it. None once every message is migrated
+    unmigrated: Option<Vector<PostedMessageV1>>,
+}
+
+//E layout of the contract state in the first version of the contract, read by migrate
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+struct ContractV1 {
+    messages: Vector<PostedMessageV1>,
+}
+
+//E storage prefix of one sender's index vector, the hash keeps prefixes the same length whatever the account id
+fn sender_prefix(sender: &AccountId) -> Vec<u8> {
+    [b"i".as_slice(), &env::sha256(sender.as_bytes())].concat()
 }
 
 //E implements the Default trait which by default create of vector of messages struct
+//E a contract used without calling new is owned by its own account
 impl Default for Contract {
     fn default() -> Self {
-        Self {
-            messages: Vector::new(b"m"),
-        }
+        Self::new(env::current_account_id(), None)
     }
 }
 
@@ -50,53 +309,666 @@ impl Default for Contract {
 #[near_bindgen]
 impl Contract {
 
+    //E initialize the contract with its owner, and the premium threshold if it isn't POINT_ONE
+    #[init]
+    pub fn new(owner: AccountId, premium_threshold: Option<NearToken>) -> Self {
+        Self {
+            messages: Vector::new(b"m"),
+            messages_by_sender: LookupMap::new(b"s"),
+            revisions: LookupMap::new(b"r"),
+            max_page_size: DEFAULT_MAX_PAGE_SIZE,
+            owner,
+            premium_threshold: premium_threshold.unwrap_or(DEFAULT_PREMIUM_THRESHOLD),
+            max_message_length: DEFAULT_MAX_MESSAGE_LENGTH,
+            refund_excess: false,
+            deposits: NearToken::from_yoctonear(0),
+            premium_messages: Vector::new(b"p"),
+            min_post_interval: DEFAULT_MIN_POST_INTERVAL,
+            moderators: LookupSet::new(b"o"),
+            flags: LookupSet::new(b"f"),
+            unmigrated: None,
+        }
+    }
+
+    //E upgrade the state of the first version of the contract, which only stored the messages
+    //E only the contract account can call it, right after deploying the new code
+    //E deposits is what the first version kept of the attached deposits, the state never counted it
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(owner: AccountId, deposits: NearToken) -> Self {
+        let old: ContractV1 = env::state_read().expect("no contract state to migrate");
+        let mut contract = Self::new(owner, None);
+
+        //E the messages are left for migrate_batch, so the upgrade costs the same gas whatever the size of the guestbook
+        //E migrating them all in this call would make a guestbook too large for the gas of one call impossible to upgrade
+        contract.unmigrated = Some(old.messages).filter(|messages| !messages.is_empty());
+
+        //E the deposits become withdrawable, they can't take the balance that pays for the storage
+        let storage_stake = env::storage_byte_cost().saturating_mul(env::storage_usage() as u128);
+        let available = env::account_balance().saturating_sub(storage_stake);
+        require!(
+            deposits <= available,
+            format!("cannot count {} of deposits, only {} of the balance is not used by storage", deposits.exact_amount_display(), available.exact_amount_display())
+        );
+        contract.deposits = deposits;
+        contract
+    }
+
+    //E migrate the next limit messages of the first version, only the owner can call it
+    //E returns the number of messages left to migrate, call it again until it returns 0
+    pub fn migrate_batch(&mut self, limit: u32) -> u32 {
+        self.assert_owner();
+        let Some(total) = self.unmigrated.as_ref().map(|messages| messages.len()) else {
+            return 0;
+        };
+
+        //E the first version stored the messages without a version under the same prefix, each one is written back as a V1
+        //E at its own index, and indexed by sender and in the premium feed like a new message
+        let from = self.messages.len();
+        let end = from.saturating_add(limit).min(total);
+        for index in from..end {
+            let message = self.unmigrated
+                .as_ref()
+                .and_then(|messages| messages.get(index))
+                .expect("index is below the number of messages to migrate")
+                .clone();
+            self.index_by_sender(&message.sender, index);
+            if message.premium {
+                self.premium_messages.push(index);
+            }
+            self.messages.push(VersionedPostedMessage::V1(message));
+        }
+
+        if end == total {
+            self.unmigrated = None;
+        }
+        total - end
+    }
+
+    //E change the deposit a message needs to be premium, only the owner can call it
+    //E messages already posted keep the premium flag they were posted with
+    pub fn set_premium_threshold(&mut self, premium_threshold: NearToken) {
+        self.assert_owner();
+        self.premium_threshold = premium_threshold;
+    }
+
+    //E change the maximum number of messages in a page, only the owner can call it
+    pub fn set_max_page_size(&mut self, max_page_size: u32) {
+        self.assert_owner();
+        require!(max_page_size > 0, "max_page_size must be at least 1");
+        self.max_page_size = max_page_size;
+    }
+
+    //E change the maximum length in bytes of a message text, only the owner can call it
+    //E messages already posted keep their text, edits of them must fit the new limit
+    pub fn set_max_message_length(&mut self, max_message_length: u32) {
+        self.assert_owner();
+        require!(max_message_length > 0, "max_message_length must be at least 1");
+        self.max_message_length = max_message_length;
+    }
+
+    //E change the time in nanoseconds an account waits between two plain messages, 0 turns the limit off, only the owner can call it
+    pub fn set_min_post_interval(&mut self, min_post_interval: U64) {
+        self.assert_owner();
+        self.min_post_interval = min_post_interval.0;
+    }
+
+    //E turn the refund of the deposit above premium_threshold on or off, only the owner can call it
+    pub fn set_refund_excess(&mut self, refund_excess: bool) {
+        self.assert_owner();
+        self.refund_excess = refund_excess;
+    }
+
+    //E get the current settings
+    pub fn get_config(&self) -> Config {
+        Config {
+            owner: self.owner.clone(),
+            premium_threshold: self.premium_threshold,
+            max_page_size: self.max_page_size,
+            max_message_length: self.max_message_length,
+            refund_excess: self.refund_excess,
+            min_post_interval: U64(self.min_post_interval),
+        }
+    }
+
+    //E allow account_id to hide messages, only the owner can call it
+    pub fn add_moderator(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.moderators.insert(account_id);
+    }
+
+    //E take the moderator role back from account_id, only the owner can call it
+    pub fn remove_moderator(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.moderators.remove(&account_id);
+    }
+
+    //E get the role of account_id, None for an account without one
+    pub fn get_role(&self, account_id: AccountId) -> Option<Role> {
+        if account_id == self.owner {
+            Some(Role::Owner)
+        } else if self.moderators.contains(&account_id) {
+            Some(Role::Moderator)
+        } else {
+            None
+        }
+    }
+
+    //E send amount of the kept deposits to the owner, all of them by default, only the owner can call it
+    pub fn withdraw(&mut self, amount: Option<NearToken>) -> Promise {
+        self.assert_owner();
+        let amount = amount.unwrap_or(self.deposits);
+        require!(amount.as_yoctonear() > 0, "nothing to withdraw");
+        require!(
+            amount <= self.deposits,
+            format!("cannot withdraw {}, only {} of deposits are withdrawable", amount.exact_amount_display(), self.deposits.exact_amount_display())
+        );
+
+        //E the amount leaves the counter before the transfer, so a second withdraw in the meantime can't spend it twice
+        self.deposits = self.deposits.saturating_sub(amount);
+        Promise::new(self.owner.clone())
+            .transfer(amount)
+            .then(Self::ext(env::current_account_id()).with_static_gas(ON_WITHDRAW_GAS).on_withdraw(amount))
+    }
+
+    //E callback of withdraw, a failed transfer gives the amount back to the counter
+    #[private]
+    pub fn on_withdraw(&mut self, amount: NearToken, #[callback_result] result: Result<(), PromiseError>) -> bool {
+        if result.is_err() {
+            self.deposits = self.deposits.saturating_add(amount);
+        }
+        result.is_ok()
+    }
+
+    //E get the deposits the owner can withdraw
+    pub fn get_withdrawable_deposits(&self) -> NearToken {
+        self.deposits
+    }
+
     //E set add_message function of contract impl to payable (allow receiving native tokens)
     #[payable]
     pub fn add_message(&mut self, text: String) {
 
-        //E check if the attached deposit (native tokens) is greater than or equal to POINT_ONE
-        let premium = env::attached_deposit() >= POINT_ONE;
+        //E a new message would be pushed over the first message not migrated yet, see unmigrated
+        require!(self.unmigrated.is_none(), "the messages of the previous version are still being migrated");
+
+        //E reject the text before anything is written
+        self.assert_valid_text(&text);
+        let storage_before = env::storage_usage();
 
         //E get the sender of the transaction
         let sender = env::predecessor_account_id();
 
-        //E create a new PostedMessage struct
+        //E when the sender last posted, read from the sender's index before the new message is in it
+        let now = env::block_timestamp();
+        let last_posted_at = self.last_posted_at(&sender);
+
+        //E create a new PostedMessage struct, its id is the index it is pushed at
+        //E premium and kept are set once the storage is paid, they don't change the size of the message
+        let index = self.messages.len();
         let message = PostedMessage {
-            premium,
-            sender,
+            id: U64(index as u64),
+            premium: false,
+            sender: sender.clone(),
             text,
+            timestamp: U64(now),
+            block_height: U64(env::block_height()),
+            deposit: env::attached_deposit(),
+            kept: NearToken::from_yoctonear(0),
+            edited_at: None,
+            deleted: false,
+            hidden: false,
+            flags: 0,
+        };
+
+        //E push the new message to the messages vector, in the current layout
+        self.messages.push(VersionedPostedMessage::V2(message));
+
+        //E and its index to the sender's index
+        self.index_by_sender(&sender, index);
+
+        //E the premium feed entry is pushed before premium is decided, so the deposit left to compare with the threshold
+        //E is what remains once the message and the entry are both paid, and it is popped again for a plain message
+        self.premium_messages.push(index);
+        let premium = env::attached_deposit()
+            .checked_sub(self.storage_cost(storage_before))
+            .is_some_and(|payment| payment >= self.premium_threshold);
+        if !premium {
+            self.premium_messages.pop();
+        }
+        let payment = self.charge_storage(storage_before, env::attached_deposit());
+
+        //E premium senders are exempt from the rate limit, they pay for each message
+        if let (false, Some(last_posted_at)) = (premium, last_posted_at) {
+            let next_post_at = last_posted_at.saturating_add(self.min_post_interval);
+            require!(
+                now >= next_post_at,
+                format!("{} posted less than {} ns ago, wait until {} or attach a premium deposit", sender, self.min_post_interval, next_post_at)
+            );
+        }
+
+        //E a plain message keeps nothing, a premium one keeps the premium threshold when refund_excess is on and the whole payment otherwise
+        let kept = match premium {
+            false => NearToken::from_yoctonear(0),
+            true if self.refund_excess => self.premium_threshold.min(payment),
+            true => payment,
+        };
+        let refund = payment.saturating_sub(kept);
+        if !refund.is_zero() {
+            Promise::new(sender).transfer(refund);
+        }
+        self.deposits = self.deposits.saturating_add(kept);
+
+        let message = self.messages.get_mut(index).expect("message was just pushed").latest_mut(index);
+        message.premium = premium;
+        message.kept = kept;
+
+        GuestbookEvent::AddMessage(vec![MessageEvent::of(message)]).emit();
+    }
+
+    //E replace the text of a message, only its sender can call it
+    //E the previous text is kept, so the sender pays for the storage the edit adds and gets the rest of the deposit back
+    #[payable]
+    pub fn edit_message(&mut self, id: U64, text: String) {
+        self.assert_valid_text(&text);
+        let storage_before = env::storage_usage();
+
+        //E the current text becomes the newest revision
+        let now = U64(env::block_timestamp());
+        let message = self.authored_message(id);
+        let revision = Revision {
+            timestamp: message.edited_at.unwrap_or(message.timestamp),
+            text: std::mem::replace(&mut message.text, text),
         };
+        message.edited_at = Some(now);
+        let event = GuestbookEvent::EditMessage(vec![MessageEvent::of(message)]);
+
+        self.revisions.entry(id.0).or_default().push(revision);
+
+        let refund = self.charge_storage(storage_before, env::attached_deposit());
+        if !refund.is_zero() {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        event.emit();
+    }
+
+    //E delete a message, only its sender can call it
+    //E the message stays in the vector as a tombstone so indexes, ids and pagination cursors don't shift
+    //E payable because a message stored by the first version is rewritten in the current layout, which can take more bytes
+    #[payable]
+    pub fn delete_message(&mut self, id: U64) {
+        let storage_before = env::storage_usage();
+        let message = self.authored_message(id);
+        message.text = String::new();
+        message.deleted = true;
+        let event = GuestbookEvent::DeleteMessage(vec![MessageEvent::of(message)]);
+
+        //E the previous texts go with it, a deleted message keeps no content
+        self.revisions.remove(&id.0);
+
+        //E the sender paid for the storage of the text and its revisions, the bytes freed are paid back
+        let left = self.charge_storage(storage_before, env::attached_deposit());
+        let freed = env::storage_byte_cost().saturating_mul(storage_before.saturating_sub(env::storage_usage()) as u128);
+        let refund = left.saturating_add(freed);
+        if !refund.is_zero() {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+        event.emit();
+    }
 
-        //E push the new message to the messages vector
-        self.messages.push(message);
+    //E hide a message, only a moderator or the owner can call it
+    pub fn hide_message(&mut self, id: U64) {
+        self.set_hidden(id, true);
+    }
+
+    //E show a hidden message again, only a moderator or the owner can call it
+    pub fn unhide_message(&mut self, id: U64) {
+        self.set_hidden(id, false);
+    }
+
+    //E report a message to the moderators, an account can flag a message once
+    //E the flag is stored, so the caller pays for its storage and gets the rest of the deposit back
+    #[payable]
+    pub fn flag_message(&mut self, id: U64) {
+        let storage_before = env::storage_usage();
+        let account = env::predecessor_account_id();
+        let index = self.message_index(id);
+
+        let message = self.messages.get_mut(index).expect("index is below the number of messages").latest_mut(index);
+        require!(!message.deleted, "message was deleted");
+        require!(self.flags.insert((index, account.clone())), "message was already flagged by this account");
+        message.flags += 1;
+
+        let refund = self.charge_storage(storage_before, env::attached_deposit());
+        if !refund.is_zero() {
+            Promise::new(account).transfer(refund);
+        }
+    }
+
+    //E get the previous texts of a message, oldest first, empty if it was never edited or is hidden
+    pub fn get_message_revisions(&self, id: U64) -> Vec<&Revision> {
+        let hidden = self.get_message(id).is_some_and(|message| message.hidden);
+        match self.revisions.get(&id.0) {
+            Some(revisions) if !hidden => revisions.iter().collect(),
+            _ => Vec::new(),
+        }
     }
 
     //E get_messages function of contract impl
+    //E from_index is an absolute index in the messages vector, so a cursor stays valid while new messages are added
     pub fn get_messages(
         &self, //E contract impl instance
-        from_index: Option<U64>, //E from index of the messages vector
-        limit: Option<U64> //E limit of the messages vector
+        from_index: Option<U64>, //E index of the first message returned, must be an existing index
+        limit: Option<U64>, //E maximum number of messages returned, capped to max_page_size
+        descending: Option<bool>, //E newest first when true, walking down from from_index
+        exclude_hidden: Option<bool> //E leave the hidden messages out of the page, the page can then hold fewer than limit messages
     ) 
-        -> Vec<&PostedMessage> //E return a vector of posted messages
+        -> MessagePage //E return a page of posted messages
     {
 
-        //E fetch message from_index (try to unwrap the from_index or set the from_index to 0 by default)
-        let from = u64::from(from_index.unwrap_or(U64(0)));
+        //E total number of messages stored, migrated or not
+        let total = self.message_count();
 
-        //E fetch message limit => try to unwrap the limit or set the limit to 10 by default
-        let limit = u64::from(limit.unwrap_or(U64(10)));
+        //E fetch message limit => try to unwrap the limit or set the limit to 10 by default, never more than max_page_size
+        let limit = self.page_limit(limit);
 
-        //E iterate over the messages vector and return the messages collected
-        self.messages
-            .iter()
-            .skip(from as usize)
-            .take(limit as usize)
-            .collect()
+        //E newest first => start from the last message by default
+        let descending = descending.unwrap_or(false);
+
+        //E an empty guestbook only has an empty first page
+        if total == 0 && from_index.is_none() {
+            return MessagePage { messages: Vec::new(), total: U64(0), next_index: None, has_more: false };
+        }
+
+        //E fetch message from_index, rejecting indexes past the end instead of returning an empty page
+        let from = match from_index {
+            Some(index) => {
+                let index = u64::from(index);
+                require!(
+                    index < total as u64,
+                    format!("from_index {} is out of range, the guestbook holds {} messages", index, total)
+                );
+                index as u32
+            }
+            None if descending => total - 1,
+            None => 0,
+        };
+
+        //E indexes of the page and the index the next page starts from
+        //E the previous version walked the vector with skip(from), the cost of a page grew with from_index
+        let (indexes, next_index) = if descending {
+            let lowest = (from + 1).saturating_sub(limit);
+            let next_index = if lowest > 0 { Some(lowest - 1) } else { None };
+            ((lowest..=from).rev().collect(), next_index)
+        } else {
+            let end = from.saturating_add(limit).min(total);
+            let next_index = if end < total { Some(end) } else { None };
+            ((from..end).collect(), next_index)
+        };
+
+        //E hidden messages are filtered out of the page instead of skipped, so a page never reads more than limit messages
+        let mut page = self.page(indexes, total, next_index);
+        if exclude_hidden.unwrap_or(false) {
+            page.messages.retain(|message| !message.hidden);
+        }
+        page
+    }
+
+    //E get a message by id, None if no message has this id
+    pub fn get_message(&self, id: U64) -> Option<PostedMessage> {
+        //E ids are vector indexes, so this is a single storage read
+        u32::try_from(u64::from(id)).ok().and_then(|index| self.public_message(index))
+    }
+
+    //E get the messages posted between from_timestamp (included) and to_timestamp (excluded), oldest first
+    //E total is the number of messages in the range, next_index the from_index of the next page
+    pub fn get_messages_in_time_range(
+        &self,
+        from_timestamp: U64, //E nanoseconds, like env::block_timestamp()
+        to_timestamp: U64,
+        from_index: Option<U64>, //E cursor returned by the previous page
+        limit: Option<U64>
+    ) -> MessagePage {
+
+        //E messages are pushed in block order, so timestamps never decrease along the vector
+        //E and both bounds of the range are found with a binary search instead of a scan
+        let lower = self.first_index_at(u64::from(from_timestamp));
+        let upper = self.first_index_at(u64::from(to_timestamp)).max(lower);
+
+        //E start at the cursor when it points inside the range
+        let from = from_index
+            .map(|index| u64::from(index).clamp(lower as u64, upper as u64) as u32)
+            .unwrap_or(lower);
+        let end = from.saturating_add(self.page_limit(limit)).min(upper);
+        let next_index = if end < upper { Some(end) } else { None };
+
+        self.page((from..end).collect(), upper - lower, next_index)
+    }
+
+    //E get the messages posted by sender, oldest first
+    //E from_index counts the sender's messages (0 is the sender's first message), not the messages vector
+    pub fn get_messages_by_sender(
+        &self,
+        sender: AccountId,
+        from_index: Option<U64>, //E must be an existing position in the sender's messages
+        limit: Option<U64>
+    ) -> MessagePage {
+
+        //E an account that never posted has an empty index
+        let Some(indexes) = self.messages_by_sender.get(&sender) else {
+            require!(from_index.is_none(), format!("from_index is out of range, {} has no messages", sender));
+            return self.page(Vec::new(), 0, None);
+        };
+        self.indexed_page(indexes, from_index, limit, sender.as_str())
+    }
+
+    //E get the number of messages posted by sender
+    pub fn total_messages_by_sender(&self, sender: AccountId) -> u32 {
+        self.messages_by_sender.get(&sender).map_or(0, |indexes| indexes.len())
+    }
+
+    //E get the premium messages, oldest first
+    //E from_index counts the premium messages (0 is the first premium message), not the messages vector
+    pub fn get_premium_messages(
+        &self,
+        from_index: Option<U64>, //E must be an existing position in the premium feed
+        limit: Option<U64>
+    ) -> MessagePage {
+        self.indexed_page(&self.premium_messages, from_index, limit, "the premium feed")
+    }
+
+    //E get the number of premium messages
+    pub fn total_premium_messages(&self) -> u32 {
+        self.premium_messages.len()
     }
 
     //E get_total_messages function of contract impl
     pub fn total_messages(&self) -> u32 {
-        self.messages.len()
+        self.message_count()
+    }
+}
+
+//E helpers of the contract methods, not exposed by the contract
+impl Contract {
+
+    //E env::predecessor_account_id() is the account calling the method
+    fn assert_owner(&self) {
+        require!(env::predecessor_account_id() == self.owner, "only the owner can call this method");
+    }
+
+    //E String is always valid UTF-8, what's left to check is what it holds
+    fn assert_valid_text(&self, text: &str) {
+        require!(!text.trim().is_empty(), "message text is blank");
+        require!(
+            text.len() <= self.max_message_length as usize,
+            format!("message text is {} bytes long, the maximum is {}", text.len(), self.max_message_length)
+        );
+        //E control and bidi characters can hide or reorder text when the guestbook is displayed
+        require!(
+            !text.chars().any(|c| (c.is_control() && c != '\n') || ('\u{202a}'..='\u{202e}').contains(&c) || ('\u{2066}'..='\u{2069}').contains(&c)),
+            "message text contains control characters"
+        );
+    }
+
+    //E write the pending changes, charge the storage they added to deposit and return what is left of it
+    //E storage is paid by the contract balance, without this anyone could lock it by posting large messages
+    fn charge_storage(&mut self, storage_before: u64, deposit: NearToken) -> NearToken {
+        let cost = self.storage_cost(storage_before);
+        let bytes = env::storage_usage().saturating_sub(storage_before);
+        require!(
+            deposit >= cost,
+            format!("attach at least {} to pay for {} bytes of storage", cost.exact_amount_display(), bytes)
+        );
+        deposit.saturating_sub(cost)
+    }
+
+    //E write the pending changes and return the cost of the storage they added since storage_before
+    fn storage_cost(&mut self, storage_before: u64) -> NearToken {
+        self.messages.flush();
+        self.messages_by_sender.flush();
+        self.revisions.flush();
+        self.flags.flush();
+        self.premium_messages.flush();
+
+        let bytes = env::storage_usage().saturating_sub(storage_before);
+        env::storage_byte_cost().saturating_mul(bytes as u128)
+    }
+
+    //E env::predecessor_account_id() is the account calling the method, the owner counts as a moderator
+    fn assert_moderator(&self) {
+        require!(
+            self.get_role(env::predecessor_account_id()).is_some(),
+            "only a moderator can hide or show messages"
+        );
+    }
+
+    //E the index of the message with this id, panics if there is none
+    fn message_index(&self, id: U64) -> u32 {
+        match u32::try_from(id.0) {
+            Ok(index) if index < self.messages.len() => index,
+            //E a message of the first version is written back at its index by migrate_batch, it can't change before
+            Ok(index) if index < self.message_count() => env::panic_str(&format!("message {} is not migrated yet", id.0)),
+            _ => env::panic_str(&format!("no message with id {}", id.0)),
+        }
+    }
+
+    fn set_hidden(&mut self, id: U64, hidden: bool) {
+        self.assert_moderator();
+        let index = self.message_index(id);
+        let message = self.messages.get_mut(index).expect("index is below the number of messages").latest_mut(index);
+        message.hidden = hidden;
+
+        let event = vec![MessageEvent::of(message)];
+        match hidden {
+            true => GuestbookEvent::HideMessage(event).emit(),
+            false => GuestbookEvent::UnhideMessage(event).emit(),
+        }
+    }
+
+    //E the message with this id, if the caller posted it and it isn't deleted or hidden
+    fn authored_message(&mut self, id: U64) -> &mut PostedMessage {
+        let index = self.message_index(id);
+        let message = self.messages.get_mut(index).expect("index is below the number of messages").latest_mut(index);
+
+        //E without this check anyone could rewrite or erase anyone's message
+        require!(message.sender == env::predecessor_account_id(), "only the sender of a message can change it");
+        require!(!message.deleted, "message was deleted");
+        //E a moderator's decision stands, the sender can't edit the text back in or delete the message to hide the fact
+        require!(!message.hidden, "message is hidden by a moderator");
+        message
+    }
+
+    //E add index to the sender's index, created on the sender's first message
+    fn index_by_sender(&mut self, sender: &AccountId, index: u32) {
+        let indexes = self.messages_by_sender
+            .entry(sender.clone())
+            .or_insert_with(|| Vector::new(sender_prefix(sender)));
+        indexes.push(index);
+        //E the sender's vector stays cached in the map, flushed now so charge_storage counts it
+        indexes.flush();
+    }
+
+    //E timestamp of the last message posted by sender, None if sender never posted
+    //E the sender's index gives the last message directly, no need to scan the messages vector
+    fn last_posted_at(&self, sender: &AccountId) -> Option<u64> {
+        let indexes = self.messages_by_sender.get(sender)?;
+        let last = *indexes.get(indexes.len().checked_sub(1)?)?;
+        self.messages.get(last).map(|message| message.timestamp())
+    }
+
+    //E a page of the messages an index (a sender's messages, the premium feed) points to, name is the index in errors
+    fn indexed_page(&self, indexes: &Vector<u32>, from_index: Option<U64>, limit: Option<U64>, name: &str) -> MessagePage {
+        let total = indexes.len();
+        let from = match from_index {
+            Some(index) => {
+                let index = u64::from(index);
+                require!(
+                    index < total as u64,
+                    format!("from_index {} is out of range, {} has {} messages", index, name, total)
+                );
+                index as u32
+            }
+            None => 0,
+        };
+        let end = from.saturating_add(self.page_limit(limit)).min(total);
+        let next_index = if end < total { Some(end) } else { None };
+
+        //E one read in the index per message, then one in the messages vector
+        let page = (from..end)
+            .map(|position| *indexes.get(position).expect("position is below the number of indexed messages"))
+            .collect();
+        self.page(page, total, next_index)
+    }
+
+    //E the requested page size, 10 by default and never more than max_page_size
+    fn page_limit(&self, limit: Option<U64>) -> u32 {
+        u64::from(limit.unwrap_or(U64(10))).min(self.max_page_size as u64) as u32
+    }
+
+    //E the number of messages, with the messages of the first version not migrated yet
+    fn message_count(&self) -> u32 {
+        self.unmigrated.as_ref().map_or(self.messages.len(), |messages| messages.len())
+    }
+
+    //E the message at index as the views return it, read in the first version's layout when it isn't migrated yet
+    //E Vector::get stops at messages.len(), so the keys the two vectors share are never read with the wrong layout
+    fn public_message(&self, index: u32) -> Option<PostedMessage> {
+        if let Some(message) = self.messages.get(index) {
+            return Some(message.public(index));
+        }
+        let message = self.unmigrated.as_ref()?.get(index)?;
+        Some(VersionedPostedMessage::V1(message.clone()).public(index))
+    }
+
+    //E read each message by index, only the messages of the page are loaded from storage
+    fn page(&self, indexes: Vec<u32>, total: u32, next_index: Option<u32>) -> MessagePage {
+        let messages = indexes
+            .into_iter()
+            .map(|index| self.public_message(index).expect("index is below the number of messages"))
+            .collect();
+
+        MessagePage {
+            messages,
+            total: U64(total as u64),
+            next_index: next_index.map(|index| U64(index as u64)),
+            has_more: next_index.is_some(),
+        }
+    }
+
+    //E index of the first message posted at or after timestamp, the number of messages if there is none
+    //E the first version didn't record timestamps, so its messages not migrated yet count as 0 like the migrated ones
+    fn first_index_at(&self, timestamp: u64) -> u32 {
+        let (mut low, mut high) = (0, self.message_count());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let posted_at = self.messages.get(middle).map_or(0, |message| message.timestamp());
+            if posted_at < timestamp {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
     }
 }
 
@@ -105,19 +977,23 @@ impl Contract {
 
 #[cfg(test)]
 mod tests {
+    use near_sdk::mock::MockAction;
+    use near_sdk::test_utils::{get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
     use super::*;
 
     //E add_message test
     #[test]
     fn add_message() {
         //E create a new contract instance
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
         let mut contract = Contract::default();
 
         //E add a message to the contract
         contract.add_message("A message".to_string());
 
         //E get the first message from the contract
-        let posted_message = &contract.get_messages(None, None)[0];
+        let posted_message = &contract.get_messages(None, None, None, None).messages[0];
 
         //E assert the message is not premium (more than 100 tokens attached to the TX)
         assert_eq!(posted_message.premium, false);
@@ -130,6 +1006,7 @@ mod tests {
     #[test]
     fn iters_messages() {
         //E create a new contract instance
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
         let mut contract = Contract::default();
 
         //E add 3 messages to the contract
@@ -144,7 +1021,7 @@ mod tests {
         assert!(*total == 3);
 
         //E get the last details message from the contract 
-        let last_message = &contract.get_messages(Some(U64::from(1)), Some(U64::from(2)))[1];
+        let last_message = &contract.get_messages(Some(U64::from(1)), Some(U64::from(2)), None, None).messages[1];
 
         //E assert the last message is not premium (more than 100 tokens attached to the TX)
         assert_eq!(last_message.premium, false);
@@ -152,4 +1029,1197 @@ mod tests {
         //E assert the last message text is "3rd message"
         assert_eq!(last_message.text, "3rd message".to_string());
     }
+
+    //E pagination metadata test
+    #[test]
+    fn paginates_messages() {
+        //E create a new contract instance with 5 messages
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        let mut contract = Contract::default();
+        for i in 0..5 {
+            contract.add_message(format!("message {}", i));
+        }
+
+        //E the first page of 2 messages points to index 2
+        let page = contract.get_messages(None, Some(U64(2)), None, None);
+        assert_eq!(page.messages.len(), 2);
+        assert_eq!(page.total, U64(5));
+        assert_eq!(page.next_index, Some(U64(2)));
+        assert!(page.has_more);
+
+        //E following next_index walks through every message exactly once
+        let mut texts = Vec::new();
+        let mut from_index = None;
+        loop {
+            let page = contract.get_messages(from_index, Some(U64(2)), None, None);
+            texts.extend(page.messages.iter().map(|message| message.text.clone()));
+            if !page.has_more {
+                assert_eq!(page.next_index, None);
+                break;
+            }
+            from_index = page.next_index;
+        }
+        assert_eq!(texts, (0..5).map(|i| format!("message {}", i)).collect::<Vec<_>>());
+
+    }
+
+    //E descending order test
+    #[test]
+    fn paginates_messages_newest_first() {
+        //E create a new contract instance with 5 messages
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        let mut contract = Contract::default();
+        for i in 0..5 {
+            contract.add_message(format!("message {}", i));
+        }
+
+        //E newest first, 2 by 2: [4, 3] then [2, 1] then [0]
+        let page = contract.get_messages(None, Some(U64(2)), Some(true), None);
+        assert_eq!(page.messages[0].text, "message 4");
+        assert_eq!(page.messages[1].text, "message 3");
+        assert_eq!(page.next_index, Some(U64(2)));
+
+        let page = contract.get_messages(page.next_index, Some(U64(2)), Some(true), None);
+        assert_eq!(page.messages[0].text, "message 2");
+        assert_eq!(page.next_index, Some(U64(0)));
+
+        let page = contract.get_messages(page.next_index, Some(U64(2)), Some(true), None);
+        assert_eq!(page.messages.len(), 1);
+        assert_eq!(page.messages[0].text, "message 0");
+        assert_eq!(page.next_index, None);
+        assert!(!page.has_more);
+
+        //E an empty guestbook has no page in either order
+        let contract = Contract::default();
+        let page = contract.get_messages(None, None, Some(true), None);
+        assert!(page.messages.is_empty());
+        assert_eq!(page.total, U64(0));
+    }
+
+    //E out of range test
+    #[test]
+    #[should_panic(expected = "from_index 5 is out of range, the guestbook holds 5 messages")]
+    fn rejects_from_index_past_the_end() {
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        let mut contract = Contract::default();
+        for i in 0..5 {
+            contract.add_message(format!("message {}", i));
+        }
+        contract.get_messages(Some(U64(5)), None, None, None);
+    }
+
+    //E out of range test, newest first
+    #[test]
+    #[should_panic(expected = "from_index 18446744073709551615 is out of range")]
+    fn rejects_huge_from_index_newest_first() {
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        let mut contract = Contract::default();
+        contract.add_message("A message".to_string());
+        contract.get_messages(Some(U64(u64::MAX)), None, Some(true), None);
+    }
+
+    //E page size cap test
+    #[test]
+    fn caps_the_page_size() {
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        let mut contract = Contract::default();
+        for i in 0..(DEFAULT_MAX_PAGE_SIZE + 10) {
+            contract.add_message(format!("message {}", i));
+        }
+
+        //E a huge limit still returns max_page_size messages, and points to the rest
+        let page = contract.get_messages(None, Some(U64(u64::MAX)), None, None);
+        assert_eq!(page.messages.len(), DEFAULT_MAX_PAGE_SIZE as usize);
+        assert_eq!(page.next_index, Some(U64(DEFAULT_MAX_PAGE_SIZE as u64)));
+
+        //E a limit of 0 returns nothing but keeps the cursor where it was
+        let page = contract.get_messages(Some(U64(3)), Some(U64(0)), None, None);
+        assert!(page.messages.is_empty());
+        assert_eq!(page.next_index, Some(U64(3)));
+    }
+
+    //E write the contract to storage, so the next state_read loads messages from storage and not from the cache
+    fn write_state(mut contract: Contract) {
+        contract.messages.flush();
+        contract.messages_by_sender.flush();
+        contract.premium_messages.flush();
+        env::state_write(&contract);
+    }
+
+    //E gas used by one get_messages call on a freshly loaded contract
+    fn page_gas(from_index: Option<U64>, limit: Option<U64>, descending: Option<bool>) -> u64 {
+        let contract: Contract = env::state_read().expect("contract state is written");
+        let before = env::used_gas();
+        contract.get_messages(from_index, limit, descending, None);
+        env::used_gas().as_gas() - before.as_gas()
+    }
+
+    //E gas cost test: the cost of a page depends on its size, not on where it starts
+    #[test]
+    fn page_gas_does_not_grow_with_from_index() {
+        let mut contract = Contract::default();
+        for i in 0..500 {
+            //E a fresh context per message, 500 messages don't fit the gas of one call
+            set_context("alice.near", STORAGE_DEPOSIT, i);
+            contract.add_message(format!("message {}", i));
+        }
+        write_state(contract);
+
+        let first = page_gas(None, Some(U64(10)), None);
+        let last = page_gas(Some(U64(490)), Some(U64(10)), None);
+        let newest = page_gas(None, Some(U64(10)), Some(true));
+
+        //E same number of storage reads wherever the page starts, up to the length of the index in the key
+        assert!(last <= first + first / 10, "page at 490 used {} gas, page at 0 used {}", last, first);
+        assert!(newest <= first + first / 10, "newest page used {} gas, page at 0 used {}", newest, first);
+
+        //E a limit above max_page_size costs exactly as much as max_page_size
+        let capped = page_gas(None, Some(U64(u64::MAX)), None);
+        let max = page_gas(None, Some(U64(DEFAULT_MAX_PAGE_SIZE as u64)), None);
+        assert_eq!(capped, max);
+
+        //E and a page costs more the more messages it holds
+        assert!(max > first);
+        assert!(Gas::from_gas(max) < Gas::from_tgas(300));
+    }
+
+    //E block metadata test
+    #[test]
+    fn records_block_metadata() {
+        let mut contract = Contract::default();
+
+        //E a premium message from bob at block 100, then a plain one from alice at block 101
+        set_context("bob.near", POINT_ONE.saturating_add(STORAGE_DEPOSIT), 100);
+        let storage_cost = add_message_storage_cost(&mut contract, "1st message");
+        set_context("alice.near", STORAGE_DEPOSIT, 101);
+        contract.add_message("2nd message".to_string());
+
+        let first = contract.get_message(U64(0)).unwrap();
+        assert_eq!(first.id, U64(0));
+        assert_eq!(first.sender, "bob.near".parse::<AccountId>().unwrap());
+        assert!(first.premium);
+        assert_eq!(first.deposit, POINT_ONE.saturating_add(STORAGE_DEPOSIT));
+        assert_eq!(first.kept, POINT_ONE.saturating_add(STORAGE_DEPOSIT).saturating_sub(storage_cost));
+        assert_eq!(first.block_height, U64(100));
+        assert_eq!(first.timestamp, U64(100 * BLOCK_TIME));
+
+        let second = contract.get_message(U64(1)).unwrap();
+        assert_eq!(second.id, U64(1));
+        assert!(!second.premium);
+        assert_eq!(second.deposit, STORAGE_DEPOSIT);
+        assert_eq!(second.kept, NearToken::from_yoctonear(0));
+        assert_eq!(second.block_height, U64(101));
+
+        //E unknown ids, including ids that don't fit the vector index
+        assert!(contract.get_message(U64(2)).is_none());
+        assert!(contract.get_message(U64(u64::MAX)).is_none());
+    }
+
+    //E time range test
+    #[test]
+    fn queries_messages_by_time_range() {
+        let mut contract = Contract::default();
+
+        //E 2 messages per block, blocks 10 to 14
+        for height in 10..15 {
+            set_context("bob.near", STORAGE_DEPOSIT, height);
+            contract.add_message(format!("block {} a", height));
+            contract.add_message(format!("block {} b", height));
+        }
+
+        //E blocks 11 and 12, the upper bound is excluded
+        let page = contract.get_messages_in_time_range(U64(11 * BLOCK_TIME), U64(13 * BLOCK_TIME), None, Some(U64(3)));
+        assert_eq!(page.total, U64(4));
+        let texts: Vec<&str> = page.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(texts, ["block 11 a", "block 11 b", "block 12 a"]);
+        assert_eq!(page.next_index, Some(U64(5)));
+
+        //E the cursor gives the rest of the range
+        let page = contract.get_messages_in_time_range(U64(11 * BLOCK_TIME), U64(13 * BLOCK_TIME), page.next_index, Some(U64(3)));
+        assert_eq!(page.messages.len(), 1);
+        assert_eq!(page.messages[0].text, "block 12 b");
+        assert!(!page.has_more);
+
+        //E ranges before, between and after the messages are empty
+        assert_eq!(contract.get_messages_in_time_range(U64(0), U64(10 * BLOCK_TIME), None, None).total, U64(0));
+        assert_eq!(contract.get_messages_in_time_range(U64(15 * BLOCK_TIME), U64(u64::MAX), None, None).total, U64(0));
+        assert!(contract.get_messages_in_time_range(U64(12 * BLOCK_TIME), U64(11 * BLOCK_TIME), None, None).messages.is_empty());
+
+        //E the whole range
+        assert_eq!(contract.get_messages_in_time_range(U64(0), U64(u64::MAX), None, None).total, U64(10));
+    }
+
+    //E per-sender index test
+    #[test]
+    fn indexes_messages_by_sender() {
+        let mut contract = Contract::default();
+        let (alice, bob): (AccountId, AccountId) = ("alice.near".parse().unwrap(), "bob.near".parse().unwrap());
+
+        //E alice and bob alternate, alice posts 3 messages and bob 2
+        for (height, sender) in ["alice.near", "bob.near", "alice.near", "bob.near", "alice.near"].iter().enumerate() {
+            set_context(sender, STORAGE_DEPOSIT, height as u64);
+            contract.add_message(format!("message {}", height));
+        }
+
+        assert_eq!(contract.total_messages_by_sender(alice.clone()), 3);
+        assert_eq!(contract.total_messages_by_sender(bob.clone()), 2);
+        assert_eq!(contract.total_messages_by_sender("carol.near".parse().unwrap()), 0);
+
+        //E every indexed message was posted by the sender, in order
+        let page = contract.get_messages_by_sender(alice.clone(), None, Some(U64(2)));
+        assert_eq!(page.total, U64(3));
+        let ids: Vec<U64> = page.messages.iter().map(|message| message.id).collect();
+        assert_eq!(ids, [U64(0), U64(2)]);
+        assert!(page.messages.iter().all(|message| message.sender == alice));
+        assert_eq!(page.next_index, Some(U64(2)));
+
+        let page = contract.get_messages_by_sender(alice.clone(), page.next_index, Some(U64(2)));
+        assert_eq!(page.messages[0].id, U64(4));
+        assert!(!page.has_more);
+
+        let page = contract.get_messages_by_sender(bob, None, None);
+        assert_eq!(page.messages.iter().map(|message| message.text.as_str()).collect::<Vec<_>>(), ["message 1", "message 3"]);
+
+        //E an account that never posted has an empty page
+        let page = contract.get_messages_by_sender("carol.near".parse().unwrap(), None, None);
+        assert!(page.messages.is_empty());
+        assert_eq!(page.total, U64(0));
+    }
+
+    //E per-sender out of range test
+    #[test]
+    #[should_panic(expected = "from_index 1 is out of range, alice.near has 1 messages")]
+    fn rejects_sender_from_index_past_the_end() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+        contract.get_messages_by_sender("alice.near".parse().unwrap(), Some(U64(1)), None);
+    }
+
+    //E per-sender index survives a round trip through storage
+    #[test]
+    fn sender_index_is_persisted() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("1st message".to_string());
+        contract.add_message("2nd message".to_string());
+        write_state(contract);
+
+        let contract: Contract = env::state_read().unwrap();
+        let page = contract.get_messages_by_sender("alice.near".parse().unwrap(), None, None);
+        assert_eq!(page.messages[1].text, "2nd message");
+    }
+
+    //E edit test
+    #[test]
+    fn author_edits_keep_revisions() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("1st version".to_string());
+
+        set_context("alice.near", STORAGE_DEPOSIT, 2);
+        contract.edit_message(U64(0), "2nd version".to_string());
+        set_context("alice.near", STORAGE_DEPOSIT, 3);
+        contract.edit_message(U64(0), "3rd version".to_string());
+
+        let message = contract.get_message(U64(0)).unwrap();
+        assert_eq!(message.text, "3rd version");
+        assert_eq!(message.edited_at, Some(U64(3 * BLOCK_TIME)));
+        assert_eq!(message.timestamp, U64(BLOCK_TIME));
+
+        //E each revision keeps the time its text was written
+        let revisions = contract.get_message_revisions(U64(0));
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].text, "1st version");
+        assert_eq!(revisions[0].timestamp, U64(BLOCK_TIME));
+        assert_eq!(revisions[1].text, "2nd version");
+        assert_eq!(revisions[1].timestamp, U64(2 * BLOCK_TIME));
+    }
+
+    //E edit access test
+    #[test]
+    #[should_panic(expected = "only the sender of a message can change it")]
+    fn only_the_sender_can_edit() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+
+        set_context("bob.near", STORAGE_DEPOSIT, 2);
+        contract.edit_message(U64(0), "Not alice's words".to_string());
+    }
+
+    //E delete access test
+    #[test]
+    #[should_panic(expected = "only the sender of a message can change it")]
+    fn only_the_sender_can_delete() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+
+        set_context("bob.near", STORAGE_DEPOSIT, 2);
+        contract.delete_message(U64(0));
+    }
+
+    //E tombstone test
+    #[test]
+    fn delete_leaves_a_tombstone() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("1st message".to_string());
+        contract.add_message("2nd message".to_string());
+        contract.add_message("3rd message".to_string());
+        contract.edit_message(U64(1), "2nd message, edited".to_string());
+
+        set_context("alice.near", NearToken::from_yoctonear(0), 2);
+        let before = env::storage_usage();
+        contract.delete_message(U64(1));
+
+        //E the storage of the text and its revision goes back to the sender
+        let freed = env::storage_byte_cost().saturating_mul((before - env::storage_usage()) as u128);
+        assert!(!freed.is_zero());
+        assert_eq!(transfers(), [("alice.near".parse().unwrap(), freed)]);
+
+        //E the message keeps its index and id, the ones after it don't move
+        assert_eq!(contract.total_messages(), 3);
+        let page = contract.get_messages(None, None, None, None);
+        assert!(page.messages[1].deleted);
+        assert_eq!(page.messages[1].id, U64(1));
+        assert_eq!(page.messages[1].text, "");
+        assert_eq!(page.messages[2].text, "3rd message");
+        assert_eq!(contract.total_messages_by_sender("alice.near".parse().unwrap()), 3);
+
+        //E and its history is gone with it
+        assert!(contract.get_message_revisions(U64(1)).is_empty());
+    }
+
+    //E deleted message test
+    #[test]
+    #[should_panic(expected = "message was deleted")]
+    fn deleted_messages_cannot_be_edited() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+        contract.delete_message(U64(0));
+        contract.edit_message(U64(0), "Back from the dead".to_string());
+    }
+
+    //E unknown message test
+    #[test]
+    #[should_panic(expected = "no message with id 7")]
+    fn unknown_messages_cannot_be_deleted() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.delete_message(U64(7));
+    }
+
+    //E init test
+    #[test]
+    fn new_sets_the_owner_and_settings() {
+        set_context("deployer.near", NearToken::from_yoctonear(0), 1);
+        let contract = Contract::new("owner.near".parse().unwrap(), Some(NearToken::from_near(1)));
+
+        let config = contract.get_config();
+        assert_eq!(config.owner, "owner.near".parse::<AccountId>().unwrap());
+        assert_eq!(config.premium_threshold, NearToken::from_near(1));
+        assert_eq!(config.max_page_size, DEFAULT_MAX_PAGE_SIZE);
+
+        //E without a threshold, POINT_ONE stays the default
+        let contract = Contract::new("owner.near".parse().unwrap(), None);
+        assert_eq!(contract.get_config().premium_threshold, POINT_ONE);
+    }
+
+    //E premium threshold test
+    #[test]
+    fn owner_changes_the_premium_threshold() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", POINT_ONE.saturating_add(STORAGE_DEPOSIT), 1);
+        contract.add_message("premium at 0.1".to_string());
+
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.set_premium_threshold(NearToken::from_near(1));
+        assert_eq!(contract.get_config().premium_threshold, NearToken::from_near(1));
+
+        //E the same deposit is no longer premium, the message posted before keeps its flag
+        set_context("alice.near", POINT_ONE.saturating_add(STORAGE_DEPOSIT), 3);
+        contract.add_message("not premium at 0.1".to_string());
+        assert!(contract.get_message(U64(0)).unwrap().premium);
+        assert!(!contract.get_message(U64(1)).unwrap().premium);
+
+        set_context("alice.near", NearToken::from_near(1).saturating_add(STORAGE_DEPOSIT), 4);
+        contract.add_message("premium at 1".to_string());
+        assert!(contract.get_message(U64(2)).unwrap().premium);
+    }
+
+    //E owner access test
+    #[test]
+    #[should_panic(expected = "only the owner can call this method")]
+    fn only_the_owner_changes_the_premium_threshold() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.set_premium_threshold(NearToken::from_yoctonear(0));
+    }
+
+    //E page size setting test
+    #[test]
+    fn owner_changes_the_max_page_size() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        for i in 0..5 {
+            contract.add_message(format!("message {}", i));
+        }
+
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.set_max_page_size(2);
+        assert_eq!(contract.get_messages(None, Some(U64(10)), None, None).messages.len(), 2);
+    }
+
+    //E upgrade the first version of the contract, counting 0.1 NEAR it kept, without migrating the messages
+    fn upgraded_contract() -> Contract {
+        write_v1_state();
+        Contract::migrate("owner.near".parse().unwrap(), POINT_ONE)
+    }
+
+    //E write the state of the first version of the contract, as it is stored on chain
+    fn write_v1_state() {
+        set_context("guestbook.near", NearToken::from_yoctonear(0), 1);
+
+        let mut old = ContractV1 { messages: Vector::new(b"m") };
+        for (premium, sender, text) in [(false, "alice.near", "1st message"), (true, "bob.near", "2nd message"), (false, "alice.near", "3rd message")] {
+            old.messages.push(PostedMessageV1 { premium, sender: sender.parse().unwrap(), text: text.to_string() });
+        }
+        old.messages.flush();
+        env::state_write(&old);
+    }
+
+    //E upgrade the first version of the contract and migrate all its messages
+    fn migrated_contract() -> Contract {
+        let mut contract = upgraded_contract();
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        assert_eq!(contract.migrate_batch(100), 0);
+        contract
+    }
+
+    //E batch migration test
+    #[test]
+    fn migrates_in_batches() {
+        let mut contract = upgraded_contract();
+        assert_eq!(contract.total_messages(), 3);
+
+        //E each batch is a separate call, the state is written and read back in between
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        assert_eq!(contract.migrate_batch(2), 1);
+        assert_eq!(contract.total_messages(), 3);
+        write_state(contract);
+
+        let mut contract: Contract = env::state_read().expect("contract state is written");
+        set_context("owner.near", NearToken::from_yoctonear(0), 3);
+        assert_eq!(contract.migrate_batch(2), 0);
+        assert_eq!(contract.migrate_batch(2), 0);
+
+        let page = contract.get_messages(None, None, None, None);
+        let texts: Vec<&str> = page.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(texts, ["1st message", "2nd message", "3rd message"]);
+        assert_eq!(contract.total_messages_by_sender("alice.near".parse().unwrap()), 2);
+        assert_eq!(contract.total_premium_messages(), 1);
+
+        //E new messages are accepted once the migration is over
+        set_context("carol.near", STORAGE_DEPOSIT, 4);
+        contract.add_message("4th message".to_string());
+        assert_eq!(contract.get_message(U64(3)).unwrap().text, "4th message");
+    }
+
+    //E views during the migration test
+    #[test]
+    fn views_the_whole_history_during_the_migration() {
+        let mut contract = upgraded_contract();
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        assert_eq!(contract.migrate_batch(1), 2);
+        write_state(contract);
+
+        //E the first message is migrated, the other two are still in the first version's layout
+        let contract: Contract = env::state_read().expect("contract state is written");
+        assert_eq!(contract.total_messages(), 3);
+        let page = contract.get_messages(None, None, None, None);
+        let texts: Vec<&str> = page.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(texts, ["1st message", "2nd message", "3rd message"]);
+        assert_eq!(page.total, U64(3));
+        assert!(!page.has_more);
+
+        let message = contract.get_message(U64(2)).unwrap();
+        assert_eq!(message.id, U64(2));
+        assert_eq!(message.sender, "alice.near".parse::<AccountId>().unwrap());
+        assert!(contract.get_message(U64(1)).unwrap().premium);
+        assert!(contract.get_message(U64(3)).is_none());
+
+        let page = contract.get_messages(Some(U64(2)), Some(U64(2)), Some(true), None);
+        let ids: Vec<U64> = page.messages.iter().map(|message| message.id).collect();
+        assert_eq!(ids, [U64(2), U64(1)]);
+        assert_eq!(contract.get_messages_in_time_range(U64(0), U64(1), None, None).total, U64(3));
+    }
+
+    //E unmigrated message change test
+    #[test]
+    #[should_panic(expected = "message 2 is not migrated yet")]
+    fn unmigrated_messages_cannot_change() {
+        let mut contract = upgraded_contract();
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.hide_message(U64(2));
+    }
+
+    //E unfinished migration test
+    #[test]
+    #[should_panic(expected = "the messages of the previous version are still being migrated")]
+    fn rejects_messages_during_the_migration() {
+        let mut contract = upgraded_contract();
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.migrate_batch(1);
+
+        set_context("carol.near", STORAGE_DEPOSIT, 3);
+        contract.add_message("would overwrite the 2nd message".to_string());
+    }
+
+    //E migration access test
+    #[test]
+    #[should_panic(expected = "only the owner can call this method")]
+    fn only_the_owner_migrates() {
+        let mut contract = upgraded_contract();
+        set_context("alice.near", NearToken::from_yoctonear(0), 2);
+        contract.migrate_batch(10);
+    }
+
+    //E pre-upgrade deposits test
+    #[test]
+    fn withdraws_the_deposits_kept_before_the_upgrade() {
+        let mut contract = migrated_contract();
+        assert_eq!(contract.get_withdrawable_deposits(), POINT_ONE);
+
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.withdraw(None);
+        assert_eq!(transfers(), [("owner.near".parse().unwrap(), POINT_ONE)]);
+        assert_eq!(contract.get_withdrawable_deposits(), NearToken::from_yoctonear(0));
+    }
+
+    //E pre-upgrade deposits above the balance test
+    #[test]
+    #[should_panic(expected = "of the balance is not used by storage")]
+    fn rejects_deposits_above_the_balance() {
+        write_v1_state();
+        Contract::migrate("owner.near".parse().unwrap(), NearToken::from_near(1_000_000));
+    }
+
+    //E migration test
+    #[test]
+    fn migrates_v1_state() {
+        let contract = migrated_contract();
+        assert_eq!(contract.total_messages(), 3);
+        assert_eq!(contract.get_config().owner, "owner.near".parse::<AccountId>().unwrap());
+
+        //E the messages keep what the first version recorded, the rest is empty
+        let message = contract.get_message(U64(1)).unwrap();
+        assert_eq!(message.id, U64(1));
+        assert_eq!(message.sender, "bob.near".parse::<AccountId>().unwrap());
+        assert_eq!(message.text, "2nd message");
+        assert!(message.premium);
+        assert_eq!(message.timestamp, U64(0));
+        assert_eq!(message.deposit, NearToken::from_yoctonear(0));
+        assert_eq!(message.kept, NearToken::from_yoctonear(0));
+
+        //E the indexes the first version didn't have are built
+        assert_eq!(contract.total_messages_by_sender("alice.near".parse().unwrap()), 2);
+        assert_eq!(contract.get_premium_messages(None, None).messages[0].id, U64(1));
+
+        //E and the migrated state loads through the new code
+        write_state(contract);
+        let contract: Contract = env::state_read().expect("contract state is written");
+        let page = contract.get_messages(None, None, None, None);
+        let texts: Vec<&str> = page.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(texts, ["1st message", "2nd message", "3rd message"]);
+    }
+
+    //E upgrade on write test
+    #[test]
+    fn upgrades_v1_messages_when_they_change() {
+        let mut contract = migrated_contract();
+
+        set_context("alice.near", STORAGE_DEPOSIT, 5);
+        contract.edit_message(U64(0), "1st message, edited".to_string());
+        contract.add_message("4th message".to_string());
+
+        let message = contract.get_message(U64(0)).unwrap();
+        assert_eq!(message.text, "1st message, edited");
+        assert_eq!(message.edited_at, Some(U64(5 * BLOCK_TIME)));
+        assert_eq!(contract.get_message_revisions(U64(0))[0].text, "1st message");
+
+        //E only the edited message was rewritten in the current layout
+        assert!(matches!(contract.messages.get(0), Some(VersionedPostedMessage::V2(_))));
+        assert!(matches!(contract.messages.get(2), Some(VersionedPostedMessage::V1(_))));
+        assert!(matches!(contract.messages.get(3), Some(VersionedPostedMessage::V2(_))));
+
+        //E the messages of the first version have no timestamp, so they stay before the new ones in time ranges
+        let page = contract.get_messages_in_time_range(U64(BLOCK_TIME), U64(u64::MAX), None, None);
+        assert_eq!(page.messages.len(), 1);
+        assert_eq!(page.messages[0].text, "4th message");
+    }
+
+    //E stored layout test: the borsh tag of the variant comes first
+    #[test]
+    fn stores_messages_with_their_version() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+        write_state(contract);
+
+        let key = [b"m".as_slice(), &0u32.to_le_bytes()].concat();
+        let bytes = env::storage_read(&key).expect("message is stored");
+        assert_eq!(bytes[0], 1);
+        let message = VersionedPostedMessage::try_from_slice(&bytes).expect("message is a VersionedPostedMessage");
+        assert!(matches!(message, VersionedPostedMessage::V2(PostedMessage { ref text, .. }) if text == "A message"));
+    }
+
+    //E NEAR sent by the transfers created in the current context, by receiver
+    fn transfers() -> Vec<(AccountId, NearToken)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver = receipt.receiver_id.clone();
+                receipt.actions.into_iter().filter_map(move |action| match action {
+                    MockAction::Transfer { deposit, .. } => Some((receiver.clone(), deposit)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    //E add a message and return the storage cost it paid
+    fn add_message_storage_cost(contract: &mut Contract, text: &str) -> NearToken {
+        let before = env::storage_usage();
+        contract.add_message(text.to_string());
+        env::storage_byte_cost().saturating_mul((env::storage_usage() - before) as u128)
+    }
+
+    //E deposit accounting test
+    #[test]
+    fn counts_kept_deposits() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", NearToken::from_near(1), 1);
+        let alice_storage = add_message_storage_cost(&mut contract, "premium");
+        assert!(transfers().is_empty());
+
+        //E a plain message only pays for its storage, the rest of the deposit goes back
+        set_context("bob.near", NearToken::from_millinear(50), 2);
+        let bob_storage = add_message_storage_cost(&mut contract, "plain");
+        assert_eq!(transfers(), [("bob.near".parse().unwrap(), NearToken::from_millinear(50).saturating_sub(bob_storage))]);
+
+        //E without refund_excess a premium message keeps everything but the storage cost
+        let kept = NearToken::from_near(1).saturating_sub(alice_storage);
+        assert_eq!(contract.get_message(U64(0)).unwrap().kept, kept);
+        assert_eq!(contract.get_message(U64(1)).unwrap().kept, NearToken::from_yoctonear(0));
+        //E deposit is what was attached, whatever part of it was kept
+        assert_eq!(contract.get_message(U64(0)).unwrap().deposit, NearToken::from_near(1));
+        assert_eq!(contract.get_message(U64(1)).unwrap().deposit, NearToken::from_millinear(50));
+        assert_eq!(contract.get_withdrawable_deposits(), kept);
+    }
+
+    //E refund test
+    #[test]
+    fn refunds_the_excess_above_the_premium_threshold() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.set_refund_excess(true);
+        assert!(contract.get_config().refund_excess);
+
+        //E 1 NEAR for a 0.1 NEAR threshold: 0.9 NEAR minus the storage cost goes back to alice
+        set_context("alice.near", NearToken::from_near(1), 2);
+        let storage_cost = add_message_storage_cost(&mut contract, "premium");
+        let refund = NearToken::from_millinear(900).saturating_sub(storage_cost);
+        assert_eq!(transfers(), [("alice.near".parse().unwrap(), refund)]);
+        assert_eq!(contract.get_message(U64(0)).unwrap().kept, POINT_ONE);
+        assert_eq!(contract.get_message(U64(0)).unwrap().deposit, NearToken::from_near(1));
+
+        //E what is left once the storage is paid must still reach the threshold
+        set_context("carol.near", POINT_ONE, 3);
+        contract.add_message("short of the threshold".to_string());
+        assert!(!contract.get_message(U64(1)).unwrap().premium);
+
+        assert_eq!(contract.get_withdrawable_deposits(), POINT_ONE);
+    }
+
+    //E premium feed entry test
+    #[test]
+    fn pays_the_premium_feed_entry_before_the_threshold() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("bob.near", NearToken::from_millinear(50), 1);
+        let plain_storage = add_message_storage_cost(&mut contract, "plain");
+
+        //E dan's message takes as many bytes as bob's, the deposit covers it and the threshold but not the feed entry
+        set_context("dan.near", plain_storage.saturating_add(POINT_ONE), 2);
+        contract.add_message("plain".to_string());
+        let message = contract.get_message(U64(1)).unwrap();
+        assert!(!message.premium);
+        assert_eq!(message.kept, NearToken::from_yoctonear(0));
+        assert_eq!(transfers(), [("dan.near".parse().unwrap(), POINT_ONE)]);
+        assert_eq!(contract.total_premium_messages(), 0);
+    }
+
+    //E storage deposit test
+    #[test]
+    #[should_panic(expected = "to pay for")]
+    fn rejects_messages_without_storage_deposit() {
+        let mut contract = Contract::default();
+        set_context("alice.near", NearToken::from_yoctonear(0), 1);
+        contract.add_message("A message".to_string());
+    }
+
+    //E the storage of a message grows with its text, and so does its cost
+    #[test]
+    fn charges_storage_by_size() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        let short = add_message_storage_cost(&mut contract, "short");
+        set_context("alice.near", STORAGE_DEPOSIT, 2);
+        let long = add_message_storage_cost(&mut contract, &"long".repeat(100));
+        assert_eq!(long.saturating_sub(short), env::storage_byte_cost().saturating_mul(400 - 5));
+
+        //E an edit pays for the revision it keeps
+        set_context("alice.near", STORAGE_DEPOSIT, 3);
+        let before = env::storage_usage();
+        contract.edit_message(U64(0), "edited".to_string());
+        let edit = env::storage_byte_cost().saturating_mul((env::storage_usage() - before) as u128);
+        assert_eq!(transfers(), [("alice.near".parse().unwrap(), STORAGE_DEPOSIT.saturating_sub(edit))]);
+    }
+
+    //E text validation test
+    #[test]
+    fn accepts_text_up_to_the_maximum_length() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.set_max_message_length(8);
+        assert_eq!(contract.get_config().max_message_length, 8);
+
+        //E 8 bytes fit, line breaks are allowed
+        set_context("alice.near", STORAGE_DEPOSIT, 2);
+        contract.add_message("8 bytes!".to_string());
+        contract.add_message("two\nrows".to_string());
+        assert_eq!(contract.get_message(U64(1)).unwrap().text, "two\nrows");
+    }
+
+    //E the maximum length counts bytes, not characters
+    #[test]
+    #[should_panic(expected = "message text is 12 bytes long, the maximum is 8")]
+    fn rejects_text_longer_than_the_maximum_length() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.set_max_message_length(8);
+
+        set_context("alice.near", STORAGE_DEPOSIT, 2);
+        contract.add_message("\u{1f600}\u{1f600}\u{1f600}".to_string());
+    }
+
+    //E blank text test
+    #[test]
+    #[should_panic(expected = "message text is blank")]
+    fn rejects_blank_text() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message(" \n\t ".to_string());
+    }
+
+    //E control characters test, a right-to-left override would display "drow" as "word"
+    #[test]
+    #[should_panic(expected = "message text contains control characters")]
+    fn rejects_control_characters() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("\u{202e}drow".to_string());
+    }
+
+    //E edit validation test
+    #[test]
+    #[should_panic(expected = "message text is blank")]
+    fn edits_are_validated() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+        contract.edit_message(U64(0), "   ".to_string());
+    }
+
+    //E withdraw test
+    #[test]
+    fn owner_withdraws_deposits() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", NearToken::from_near(1), 1);
+        contract.add_message("premium".to_string());
+        let kept = contract.get_withdrawable_deposits();
+
+        //E part of the deposits, then the rest
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.withdraw(Some(NearToken::from_millinear(400)));
+        assert_eq!(contract.get_withdrawable_deposits(), kept.saturating_sub(NearToken::from_millinear(400)));
+        assert_eq!(transfers(), [("owner.near".parse().unwrap(), NearToken::from_millinear(400))]);
+
+        set_context("owner.near", NearToken::from_yoctonear(0), 3);
+        contract.withdraw(None);
+        assert_eq!(contract.get_withdrawable_deposits(), NearToken::from_yoctonear(0));
+        assert_eq!(transfers(), [("owner.near".parse().unwrap(), kept.saturating_sub(NearToken::from_millinear(400)))]);
+    }
+
+    //E failed withdraw test
+    #[test]
+    fn failed_withdraw_restores_the_deposits() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", NearToken::from_near(1), 1);
+        contract.add_message("premium".to_string());
+
+        let kept = contract.get_withdrawable_deposits();
+
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.withdraw(None);
+        assert_eq!(contract.get_withdrawable_deposits(), NearToken::from_yoctonear(0));
+
+        //E the callback runs as the contract itself
+        set_context("guestbook.near", NearToken::from_yoctonear(0), 3);
+        assert!(!contract.on_withdraw(kept, Err(PromiseError::Failed)));
+        assert_eq!(contract.get_withdrawable_deposits(), kept);
+    }
+
+    //E withdraw access test
+    #[test]
+    #[should_panic(expected = "only the owner can call this method")]
+    fn only_the_owner_withdraws() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", NearToken::from_near(1), 1);
+        contract.add_message("premium".to_string());
+        contract.withdraw(None);
+    }
+
+    //E withdraw limit test
+    #[test]
+    #[should_panic(expected = "cannot withdraw 2 NEAR")]
+    fn cannot_withdraw_more_than_the_deposits() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", NearToken::from_near(1), 1);
+        contract.add_message("premium".to_string());
+
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.withdraw(Some(NearToken::from_near(2)));
+    }
+
+    //E covers the storage of any message of the tests, what is left is refunded
+    const STORAGE_DEPOSIT: NearToken = NearToken::from_millinear(10);
+
+    //E event log test
+    #[test]
+    fn logs_nep297_events() {
+        let mut contract = Contract::default();
+        set_context("alice.near", POINT_ONE.saturating_add(STORAGE_DEPOSIT), 1);
+        contract.add_message("A message".to_string());
+        assert_eq!(
+            get_logs(),
+            [r#"EVENT_JSON:{"standard":"guestbook","version":"1.0.0","event":"add_message","data":[{"index":"0","sender":"alice.near","premium":true}]}"#]
+        );
+
+        set_context("bob.near", STORAGE_DEPOSIT, 2);
+        contract.add_message("Another message".to_string());
+        contract.edit_message(U64(1), "Another message, edited".to_string());
+        contract.delete_message(U64(1));
+        assert_eq!(
+            get_logs(),
+            [
+                r#"EVENT_JSON:{"standard":"guestbook","version":"1.0.0","event":"add_message","data":[{"index":"1","sender":"bob.near","premium":false}]}"#,
+                r#"EVENT_JSON:{"standard":"guestbook","version":"1.0.0","event":"edit_message","data":[{"index":"1","sender":"bob.near","premium":false}]}"#,
+                r#"EVENT_JSON:{"standard":"guestbook","version":"1.0.0","event":"delete_message","data":[{"index":"1","sender":"bob.near","premium":false}]}"#,
+            ]
+        );
+    }
+
+    //E views log nothing
+    #[test]
+    fn views_log_no_events() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+
+        set_context("alice.near", NearToken::from_yoctonear(0), 2);
+        contract.get_messages(None, None, None, None);
+        contract.get_message(U64(0));
+        assert!(get_logs().is_empty());
+    }
+
+    //E roles test
+    #[test]
+    fn owner_manages_moderators() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.add_moderator("mod.near".parse().unwrap());
+
+        assert_eq!(contract.get_role("owner.near".parse().unwrap()), Some(Role::Owner));
+        assert_eq!(contract.get_role("mod.near".parse().unwrap()), Some(Role::Moderator));
+        assert_eq!(contract.get_role("alice.near".parse().unwrap()), None);
+
+        contract.remove_moderator("mod.near".parse().unwrap());
+        assert_eq!(contract.get_role("mod.near".parse().unwrap()), None);
+    }
+
+    //E moderator access test
+    #[test]
+    #[should_panic(expected = "only the owner can call this method")]
+    fn only_the_owner_adds_moderators() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", NearToken::from_yoctonear(0), 1);
+        contract.add_moderator("alice.near".parse().unwrap());
+    }
+
+    //E post three messages as alice and hide the second one as the owner
+    fn contract_with_a_hidden_message() -> Contract {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        for i in 0..3 {
+            contract.add_message(format!("message {}", i));
+        }
+        set_context("owner.near", NearToken::from_yoctonear(0), 2);
+        contract.hide_message(U64(1));
+        contract
+    }
+
+    //E hidden message edit test
+    #[test]
+    #[should_panic(expected = "message is hidden by a moderator")]
+    fn hidden_messages_cannot_be_edited() {
+        let mut contract = contract_with_a_hidden_message();
+        set_context("alice.near", STORAGE_DEPOSIT, 3);
+        contract.edit_message(U64(1), "message 1, again".to_string());
+    }
+
+    //E hidden message delete test
+    #[test]
+    #[should_panic(expected = "message is hidden by a moderator")]
+    fn hidden_messages_cannot_be_deleted() {
+        let mut contract = contract_with_a_hidden_message();
+        set_context("alice.near", NearToken::from_yoctonear(0), 3);
+        contract.delete_message(U64(1));
+    }
+
+    //E hide test
+    #[test]
+    fn moderators_hide_messages() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.add_moderator("mod.near".parse().unwrap());
+        set_context("alice.near", STORAGE_DEPOSIT, 2);
+        for i in 0..3 {
+            contract.add_message(format!("message {}", i));
+        }
+
+        set_context("mod.near", NearToken::from_yoctonear(0), 3);
+        contract.hide_message(U64(1));
+        assert!(contract.get_message(U64(1)).unwrap().hidden);
+        assert_eq!(
+            get_logs(),
+            [r#"EVENT_JSON:{"standard":"guestbook","version":"1.0.0","event":"hide_message","data":[{"index":"1","sender":"alice.near","premium":false}]}"#]
+        );
+
+        //E hidden messages are in the page unless they are excluded, the cursor doesn't change
+        let page = contract.get_messages(None, Some(U64(2)), None, None);
+        assert_eq!(page.messages.len(), 2);
+        let page = contract.get_messages(None, Some(U64(2)), None, Some(true));
+        let texts: Vec<&str> = page.messages.iter().map(|message| message.text.as_str()).collect();
+        assert_eq!(texts, ["message 0"]);
+        assert_eq!(page.next_index, Some(U64(2)));
+        assert_eq!(page.total, U64(3));
+
+        //E the owner can show it again, with its text
+        set_context("owner.near", NearToken::from_yoctonear(0), 4);
+        contract.unhide_message(U64(1));
+        let message = contract.get_message(U64(1)).unwrap();
+        assert!(!message.hidden);
+        assert_eq!(message.text, "message 1");
+    }
+
+    //E hidden text test
+    #[test]
+    fn views_redact_hidden_messages() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", POINT_ONE.saturating_add(STORAGE_DEPOSIT), 1);
+        contract.add_message("first text".to_string());
+        set_context("alice.near", STORAGE_DEPOSIT, 2);
+        contract.edit_message(U64(0), "offensive text".to_string());
+
+        set_context("owner.near", NearToken::from_yoctonear(0), 3);
+        contract.hide_message(U64(0));
+
+        //E every view returns the message, hidden and without its text
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let pages = [
+            contract.get_messages(None, None, None, None),
+            contract.get_messages_by_sender(alice.clone(), None, None),
+            contract.get_messages_in_time_range(U64(0), U64(u64::MAX), None, None),
+            contract.get_premium_messages(None, None),
+        ];
+        for page in pages {
+            assert_eq!(page.messages.len(), 1);
+            assert!(page.messages[0].hidden);
+            assert_eq!(page.messages[0].text, "");
+        }
+        let message = contract.get_message(U64(0)).unwrap();
+        assert_eq!((message.sender, message.text), (alice, String::new()));
+        assert!(contract.get_message_revisions(U64(0)).is_empty());
+
+        //E showing it again brings the text and the revisions back
+        contract.unhide_message(U64(0));
+        assert_eq!(contract.get_message(U64(0)).unwrap().text, "offensive text");
+        assert_eq!(contract.get_message_revisions(U64(0))[0].text, "first text");
+    }
+
+    //E hide access test
+    #[test]
+    #[should_panic(expected = "only a moderator can hide or show messages")]
+    fn only_moderators_hide_messages() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+
+        set_context("bob.near", NearToken::from_yoctonear(0), 2);
+        contract.hide_message(U64(0));
+    }
+
+    //E removed moderator test
+    #[test]
+    #[should_panic(expected = "only a moderator can hide or show messages")]
+    fn removed_moderators_cannot_hide_messages() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.add_moderator("mod.near".parse().unwrap());
+        contract.remove_moderator("mod.near".parse().unwrap());
+        set_context("alice.near", STORAGE_DEPOSIT, 2);
+        contract.add_message("A message".to_string());
+
+        set_context("mod.near", NearToken::from_yoctonear(0), 3);
+        contract.hide_message(U64(0));
+    }
+
+    //E flag test
+    #[test]
+    fn accounts_flag_messages() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+
+        set_context("bob.near", STORAGE_DEPOSIT, 2);
+        contract.flag_message(U64(0));
+        set_context("carol.near", STORAGE_DEPOSIT, 3);
+        contract.flag_message(U64(0));
+        assert_eq!(contract.get_message(U64(0)).unwrap().flags, 2);
+
+        //E carol paid for the storage of her flag and got the rest back
+        assert_eq!(transfers().len(), 1);
+        assert!(transfers()[0].1 < STORAGE_DEPOSIT);
+    }
+
+    //E flag once test
+    #[test]
+    #[should_panic(expected = "message was already flagged by this account")]
+    fn accounts_flag_a_message_once() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+
+        set_context("bob.near", STORAGE_DEPOSIT, 2);
+        contract.flag_message(U64(0));
+        contract.flag_message(U64(0));
+    }
+
+    //E unknown message flag test
+    #[test]
+    #[should_panic(expected = "no message with id 3")]
+    fn unknown_messages_cannot_be_flagged() {
+        let mut contract = Contract::default();
+        set_context("bob.near", STORAGE_DEPOSIT, 1);
+        contract.flag_message(U64(3));
+    }
+
+    //E premium feed test
+    #[test]
+    fn indexes_premium_messages() {
+        let mut contract = Contract::default();
+        for (height, deposit) in [STORAGE_DEPOSIT, POINT_ONE.saturating_add(STORAGE_DEPOSIT)].repeat(3).into_iter().enumerate() {
+            set_context("alice.near", deposit, height as u64);
+            contract.add_message(format!("message {}", height));
+        }
+        assert_eq!(contract.total_premium_messages(), 3);
+
+        //E only the premium messages, 2 by 2
+        let page = contract.get_premium_messages(None, Some(U64(2)));
+        let ids: Vec<U64> = page.messages.iter().map(|message| message.id).collect();
+        assert_eq!(ids, [U64(1), U64(3)]);
+        assert!(page.messages.iter().all(|message| message.premium));
+        assert_eq!(page.total, U64(3));
+        assert_eq!(page.next_index, Some(U64(2)));
+
+        let page = contract.get_premium_messages(page.next_index, Some(U64(2)));
+        assert_eq!(page.messages[0].id, U64(5));
+        assert!(!page.has_more);
+    }
+
+    //E premium feed out of range test
+    #[test]
+    #[should_panic(expected = "from_index 0 is out of range, the premium feed has 0 messages")]
+    fn rejects_premium_from_index_past_the_end() {
+        let mut contract = Contract::default();
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("A message".to_string());
+        contract.get_premium_messages(Some(U64(0)), None);
+    }
+
+    //E rate limit test
+    #[test]
+    fn rate_limits_plain_messages() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.set_min_post_interval(U64(10 * BLOCK_TIME));
+        assert_eq!(contract.get_config().min_post_interval, U64(10 * BLOCK_TIME));
+
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("1st message".to_string());
+
+        //E bob isn't limited by alice, and a premium message isn't limited at all
+        set_context("bob.near", STORAGE_DEPOSIT, 2);
+        contract.add_message("bob's message".to_string());
+        set_context("alice.near", POINT_ONE.saturating_add(STORAGE_DEPOSIT), 3);
+        contract.add_message("premium message".to_string());
+
+        //E 10 blocks after alice's last message
+        set_context("alice.near", STORAGE_DEPOSIT, 13);
+        contract.add_message("2nd plain message".to_string());
+        assert_eq!(contract.total_messages_by_sender("alice.near".parse().unwrap()), 3);
+    }
+
+    //E rate limit reached test
+    #[test]
+    #[should_panic(expected = "alice.near posted less than 10000000000 ns ago, wait until 11000000000")]
+    fn rejects_plain_messages_posted_too_soon() {
+        let mut contract = Contract::new("owner.near".parse().unwrap(), None);
+        set_context("owner.near", NearToken::from_yoctonear(0), 1);
+        contract.set_min_post_interval(U64(10 * BLOCK_TIME));
+
+        set_context("alice.near", STORAGE_DEPOSIT, 1);
+        contract.add_message("1st message".to_string());
+        set_context("alice.near", STORAGE_DEPOSIT, 5);
+        contract.add_message("2nd message".to_string());
+    }
+
+    //E nanoseconds between two blocks in the mocked context
+    const BLOCK_TIME: u64 = 1_000_000_000;
+
+    //E Auxiliar fn: create a mock context
+    fn set_context(predecessor: &str, deposit: NearToken, block_height: u64) {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .attached_deposit(deposit)
+            .current_account_id("guestbook.near".parse().unwrap())
+            .block_height(block_height)
+            .block_timestamp(block_height * BLOCK_TIME);
+
+        testing_env!(builder.build());
+    }
 }
\ No newline at end of file